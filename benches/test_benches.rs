@@ -7,6 +7,9 @@ use rdd::expr_rc;
 use rdd::naive_bdd;
 use rdd::neg_arc_bdd;
 use rdd::combin_bdd;
+use rdd::packed_bdd;
+use rdd::par_bdd;
+use rdd::manager::Manager;
 use std::time::Duration;
 
 macro_rules! mk_group {
@@ -472,6 +475,158 @@ fn all_for_key(c: &mut Criterion) {
     group.finish();
 }
 
+fn combinatorial_vs_ite(c: &mut Criterion) {
+    mk_group!(group, c, "combinatorial_vs_ite", 10);
+    for bits in [4, 6, 8, 10, 12, 14, 16, 18].iter() {
+        group.bench_with_input(BenchmarkId::new("Combinatorial (Degenerate order)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	combin_bdd::from_combinatorial_hash(&comp, &ord_bad)
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("Combinatorial (Ideal order)", bits), &bits, |b, &bits| {
+            let (comp, _, ord_good) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	combin_bdd::from_combinatorial_hash(&comp, &ord_good)
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("ITE Manager (Degenerate order)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	Manager::new(&ord_bad).from_expr(&comp)
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("ITE Manager (Ideal order)", bits), &bits, |b, &bits| {
+            let (comp, _, ord_good) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	Manager::new(&ord_good).from_expr(&comp)
+            });
+        });
+    }
+    group.finish();
+}
+
+fn fixed_vs_sifted(c: &mut Criterion) {
+    mk_group!(group, c, "fixed_vs_sifted", 10);
+    for bits in [4, 6, 8, 10, 12].iter() {
+        group.bench_with_input(BenchmarkId::new("ITE Manager (Degenerate order)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	Manager::new(&ord_bad).from_expr(&comp)
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("ITE Manager (Degenerate order, sifted)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	let mut manager = Manager::new(&ord_bad);
+            	let f = manager.from_expr(&comp);
+            	manager.protect(f);
+            	manager.sift();
+            	f
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("ITE Manager (Degenerate order, auto reorder)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	let mut manager = Manager::new(&ord_bad);
+            	manager.set_auto_reorder(Some(1000));
+            	manager.from_expr(&comp)
+            });
+        });
+    }
+    group.finish();
+}
+
+fn cache_sizes(c: &mut Criterion) {
+    mk_group!(group, c, "cache_sizes", 10);
+    for bits in [8, 10, 12, 14].iter() {
+        for slots in [1 << 10, 1 << 14, 1 << 18].iter() {
+            group.bench_with_input(BenchmarkId::new(format!("Combinatorial Hash ({} slot cache, Degenerate order)", slots), bits), &bits, |b, &bits| {
+                let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+                b.iter(|| {
+                	combin_bdd::from_combinatorial_hash_cached(&comp, &ord_bad, &mut combin_bdd::AndCache::new(*slots))
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+fn usize_vs_packed_layout(c: &mut Criterion) {
+    mk_group!(group, c, "usize_vs_packed_layout", 10);
+    for bits in [4, 6, 8, 10, 12, 14].iter() {
+        group.bench_with_input(BenchmarkId::new("Construction (usize nodes, Degenerate order)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	combin_bdd::from_combinatorial_hash(&comp, &ord_bad)
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("Construction (packed nodes, Degenerate order)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	packed_bdd::from_combinatorial_hash(&comp, &ord_bad)
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("Eval (usize nodes, Degenerate order)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            let bdd = combin_bdd::from_combinatorial_hash(&comp, &ord_bad);
+            let envs: Vec<expr::Env> = (0..256u32).map(|i| (0..2 * bits).map(|x| (i.wrapping_mul(2654435761) >> (x % 32)) & 1 == 1).collect()).collect();
+            b.iter(|| {
+            	envs.iter().filter(|env| bdd.eval(env)).count()
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("Eval (packed nodes, Degenerate order)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            let bdd = packed_bdd::from_combinatorial_hash(&comp, &ord_bad);
+            let envs: Vec<expr::Env> = (0..256u32).map(|i| (0..2 * bits).map(|x| (i.wrapping_mul(2654435761) >> (x % 32)) & 1 == 1).collect()).collect();
+            b.iter(|| {
+            	envs.iter().filter(|env| bdd.eval(env)).count()
+            });
+        });
+    }
+    group.finish();
+}
+
+fn sequential_vs_parallel(c: &mut Criterion) {
+    mk_group!(group, c, "sequential_vs_parallel", 10);
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    for bits in [4, 6, 8, 10, 12, 14].iter() {
+        group.bench_with_input(BenchmarkId::new("Sequential (Degenerate order)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	combin_bdd::from_combinatorial_hash(&comp, &ord_bad)
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("Parallel (Degenerate order)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	par_bdd::from_combinatorial_par(&comp, &ord_bad, threads)
+            });
+        });
+    }
+    group.finish();
+}
+
+fn shannon_sequential_vs_parallel(c: &mut Criterion) {
+    mk_group!(group, c, "shannon_sequential_vs_parallel", 10);
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    for bits in [2, 4, 6, 8].iter() {
+        group.bench_with_input(BenchmarkId::new("Support Simplified (Degenerate order)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	neg_arc_bdd::from_support_simplified(&comp, &ord_bad)
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("Support Simplified Parallel (Degenerate order)", bits), &bits, |b, &bits| {
+            let (comp, ord_bad, _) = expr::gen::comparator(*bits);
+            b.iter(|| {
+            	par_bdd::from_support_simplified_par(&comp, &ord_bad, 4, threads)
+            });
+        });
+    }
+    group.finish();
+}
+
 // fn hash_vs_btree_lookup(c: &mut Criterion) {
 //     mk_group!(group, c, "hash_vs_btree_lookup", 10);
 //     for bits in [4, 6, 8, 10, 12].iter() {
@@ -504,5 +659,11 @@ criterion_group!(benches, clone_box_vs_rc,
 	                      combinatorial_btree_vs_hash,
 	                      all_degenerate,
 	                      all_ideal,
-	                      all_for_key);
+	                      all_for_key,
+	                      combinatorial_vs_ite,
+	                      fixed_vs_sifted,
+	                      cache_sizes,
+	                      usize_vs_packed_layout,
+	                      sequential_vs_parallel,
+	                      shannon_sequential_vs_parallel);
 criterion_main!(benches);
@@ -0,0 +1,236 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+// Arbitrary precision unsigned integer, just enough arithmetic for counting
+// assignments of bdds with more than 64 variables.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BigUint {
+	// base 2^32 digits, least significant first, no trailing zero digits
+	digits: Vec<u32>,
+}
+
+impl BigUint {
+
+	pub fn zero() -> BigUint {
+		BigUint { digits: Vec::new() }
+	}
+
+	pub fn one() -> BigUint {
+		BigUint::from(1)
+	}
+
+	// 2^k
+	pub fn pow2(k: usize) -> BigUint {
+		BigUint::one().shl(k)
+	}
+
+	// base 2^32 digits, least significant first
+	pub fn from_digits(digits: Vec<u32>) -> BigUint {
+		BigUint::trimmed(digits)
+	}
+
+	pub fn is_zero(&self) -> bool {
+		self.digits.is_empty()
+	}
+
+	// number of bits needed to write self
+	pub fn bits(&self) -> usize {
+		match self.digits.last() {
+			Some(d) => 32 * self.digits.len() - d.leading_zeros() as usize,
+			None => 0,
+		}
+	}
+
+	pub fn add(&self, other: &BigUint) -> BigUint {
+		let len = self.digits.len().max(other.digits.len());
+		let mut digits = Vec::with_capacity(len + 1);
+		let mut carry = 0u64;
+		for i in 0..len {
+			let sum = self.digit(i) as u64 + other.digit(i) as u64 + carry;
+			digits.push(sum as u32);
+			carry = sum >> 32;
+		}
+		digits.push(carry as u32);
+		BigUint::trimmed(digits)
+	}
+
+	// self - other, panics if other > self
+	pub fn sub(&self, other: &BigUint) -> BigUint {
+		assert!(*self >= *other, "BigUint subtraction underflow");
+		let mut digits = Vec::with_capacity(self.digits.len());
+		let mut borrow = 0i64;
+		for i in 0..self.digits.len() {
+			let mut diff = self.digit(i) as i64 - other.digit(i) as i64 - borrow;
+			borrow = if diff < 0 { diff += 1 << 32; 1 } else { 0 };
+			digits.push(diff as u32);
+		}
+		BigUint::trimmed(digits)
+	}
+
+	pub fn mul(&self, other: &BigUint) -> BigUint {
+		let mut digits = vec![0u32; self.digits.len() + other.digits.len()];
+		for (i, &a) in self.digits.iter().enumerate() {
+			let mut carry = 0u64;
+			for (j, &b) in other.digits.iter().enumerate() {
+				let cur = digits[i + j] as u64 + a as u64 * b as u64 + carry;
+				digits[i + j] = cur as u32;
+				carry = cur >> 32;
+			}
+			digits[i + other.digits.len()] = carry as u32;
+		}
+		BigUint::trimmed(digits)
+	}
+
+	// self * 2^k
+	pub fn shl(&self, k: usize) -> BigUint {
+		if self.is_zero() {
+			return BigUint::zero();
+		}
+		let (words, bits) = (k / 32, k % 32);
+		let mut digits = vec![0; words];
+		let mut carry = 0u32;
+		for &d in &self.digits {
+			digits.push(if bits == 0 { d } else { (d << bits) | carry });
+			carry = if bits == 0 { 0 } else { d >> (32 - bits) };
+		}
+		digits.push(carry);
+		BigUint::trimmed(digits)
+	}
+
+	// self / 2^k, rounding down
+	pub fn shr(&self, k: usize) -> BigUint {
+		let (words, bits) = (k / 32, k % 32);
+		if words >= self.digits.len() {
+			return BigUint::zero();
+		}
+		let digits = (words..self.digits.len())
+			.map(|i| {
+				let hi = if bits == 0 { 0 } else { self.digit(i + 1) << (32 - bits) };
+				(self.digit(i) >> bits) | hi
+			})
+			.collect();
+		BigUint::trimmed(digits)
+	}
+
+	// closest f64, infinite past f64::MAX
+	pub fn to_f64(&self) -> f64 {
+		self.digits.iter().rev().fold(0.0, |acc, &d| acc * 4294967296.0 + d as f64)
+	}
+
+	fn digit(&self, i: usize) -> u32 {
+		*self.digits.get(i).unwrap_or(&0)
+	}
+
+	fn trimmed(mut digits: Vec<u32>) -> BigUint {
+		while digits.last() == Some(&0) {
+			digits.pop();
+		}
+		BigUint { digits }
+	}
+
+	// (self / d, self % d) for a single digit divisor
+	fn div_rem_digit(&self, d: u32) -> (BigUint, u32) {
+		let mut digits = vec![0; self.digits.len()];
+		let mut rem = 0u64;
+		for i in (0..self.digits.len()).rev() {
+			let cur = (rem << 32) | self.digits[i] as u64;
+			digits[i] = (cur / d as u64) as u32;
+			rem = cur % d as u64;
+		}
+		(BigUint::trimmed(digits), rem as u32)
+	}
+}
+
+impl From<u64> for BigUint {
+	fn from(n: u64) -> BigUint {
+		BigUint::trimmed(vec![n as u32, (n >> 32) as u32])
+	}
+}
+
+impl Ord for BigUint {
+	fn cmp(&self, other: &BigUint) -> Ordering {
+		self.digits.len().cmp(&other.digits.len())
+			.then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+	}
+}
+
+impl PartialOrd for BigUint {
+	fn partial_cmp(&self, other: &BigUint) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl fmt::Display for BigUint {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.is_zero() {
+			return write!(f, "0");
+		}
+		// peel off base 10^9 chunks, least significant first
+		let mut chunks = Vec::new();
+		let mut n = self.clone();
+		while !n.is_zero() {
+			let (q, r) = n.div_rem_digit(1_000_000_000);
+			chunks.push(r);
+			n = q;
+		}
+		write!(f, "{}", chunks.pop().unwrap())?;
+		for chunk in chunks.iter().rev() {
+			write!(f, "{:09}", chunk)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rng::{Rng, SplitMix64};
+
+	fn big(n: u128) -> BigUint {
+		BigUint::from_digits((0..4).map(|i| (n >> (32 * i)) as u32).collect())
+	}
+
+	#[test]
+	fn carries_and_borrows() {
+		let max = BigUint::from(u64::MAX);
+		assert_eq!(max.add(&BigUint::one()), BigUint::pow2(64));
+		assert_eq!(BigUint::pow2(64).sub(&BigUint::one()), max);
+		// a borrow running through every digit
+		assert_eq!(BigUint::pow2(128).sub(&BigUint::one()), big(u128::MAX));
+		assert_eq!(big(u128::MAX).add(&BigUint::one()), BigUint::pow2(128));
+		assert!(max.sub(&max).is_zero());
+		assert_eq!(max.mul(&max), big(u64::MAX as u128 * u64::MAX as u128));
+	}
+
+	#[test]
+	fn arithmetic_matches_u128() {
+		let mut rng = SplitMix64::new(10);
+		for _ in 0..1000 {
+			// mixed widths, so digit counts differ
+			let a = (rng.next_u64() as u128) << (rng.next_u64() % 64);
+			let b = (rng.next_u64() >> (rng.next_u64() % 64)) as u128;
+			let (hi, lo) = if a < b { (b, a) } else { (a, b) };
+			assert_eq!(big(hi).add(&big(lo)), big(hi + lo));
+			assert_eq!(big(hi).sub(&big(lo)), big(hi - lo));
+			assert_eq!(big(lo >> 64).mul(&big(b)), big((lo >> 64) * b));
+			let k = (rng.next_u64() % 64) as usize;
+			assert_eq!(big(b).shl(k), big(b << k));
+			assert_eq!(big(a).shr(k), big(a >> k));
+			assert_eq!(big(a).cmp(&big(b)), a.cmp(&b));
+			assert_eq!(big(a).to_string(), a.to_string());
+			assert_eq!(big(a).bits(), (128 - a.leading_zeros()) as usize);
+		}
+		assert_eq!(BigUint::pow2(100).shr(37), BigUint::pow2(63));
+		assert!(BigUint::pow2(100).shr(101).is_zero());
+	}
+
+	#[test]
+	fn display_pads_inner_chunks() {
+		assert_eq!(BigUint::zero().to_string(), "0");
+		assert_eq!(BigUint::from(999_999_999).to_string(), "999999999");
+		assert_eq!(BigUint::from(1_000_000_000).to_string(), "1000000000");
+		assert_eq!(BigUint::from(1_000_000_000_000_000_005).to_string(), "1000000000000000005");
+		assert_eq!(BigUint::pow2(128).to_string(), "340282366920938463463374607431768211456");
+		assert_eq!(BigUint::pow2(200).to_string(), "1606938044258990275541962092341162602522202993782792835301376");
+	}
+}
@@ -0,0 +1,131 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// Slots a cache starts out with, it doubles up to its size as it fills
+const INITIAL_SLOTS: usize = 1 << 10;
+
+// Default size of the computed table of a manager or construction, in slots
+pub const DEFAULT_CACHE_SLOTS: usize = 1 << 18;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+	pub hits: u64,
+	pub misses: u64,
+	pub inserts: u64,
+	// inserts that overwrote a different key
+	pub evictions: u64,
+}
+
+impl CacheStats {
+
+	// fraction of lookups that hit, 0 before any lookup
+	pub fn hit_rate(&self) -> f64 {
+		let lookups = self.hits + self.misses;
+		if lookups == 0 { 0.0 } else { self.hits as f64 / lookups as f64 }
+	}
+}
+
+// Direct-mapped lossy cache for computed tables.
+// Each key hashes to a single slot and a new result simply overwrites
+// whatever was there, so memory stays bounded no matter how long a
+// construction runs. Forgetting a result only costs recomputing it.
+#[derive(Debug)]
+pub struct ComputedCache<K, V> {
+	slots: Vec<Option<(K, V)>>,
+	// slots never grow past this, a power of 2
+	max_slots: usize,
+	// inserts since the last resize, the table doubles once they pass its size
+	fills: usize,
+	stats: CacheStats,
+}
+
+impl<K: Hash + Eq, V: Clone> ComputedCache<K, V> {
+
+	// cache holding at most max_slots entries, rounded up to a power of 2
+	pub fn new(max_slots: usize) -> ComputedCache<K, V> {
+		let max_slots = max_slots.max(1).next_power_of_two();
+		ComputedCache {
+			slots: (0..max_slots.min(INITIAL_SLOTS)).map(|_| None).collect(),
+			max_slots,
+			fills: 0,
+			stats: CacheStats::default(),
+		}
+	}
+
+	pub fn get(&mut self, key: &K) -> Option<V> {
+		let i = self.slot(key);
+		match &self.slots[i] {
+			Some((k, v)) if k == key => {
+				self.stats.hits += 1;
+				Some(v.clone())
+			},
+			_ => {
+				self.stats.misses += 1;
+				None
+			},
+		}
+	}
+
+	pub fn insert(&mut self, key: K, value: V) {
+		self.fills += 1;
+		if self.fills > self.slots.len() && self.slots.len() < self.max_slots {
+			self.grow();
+		}
+		let i = self.slot(&key);
+		if let Some((k, _)) = &self.slots[i] {
+			if *k != key {
+				self.stats.evictions += 1;
+			}
+		}
+		self.slots[i] = Some((key, value));
+		self.stats.inserts += 1;
+	}
+
+	pub fn clear(&mut self) {
+		for slot in self.slots.iter_mut() {
+			*slot = None;
+		}
+		self.fills = 0;
+	}
+
+	// keep only the entries f accepts
+	pub fn retain<F>(&mut self, mut f: F) where F: FnMut(&K, &V) -> bool {
+		for slot in self.slots.iter_mut() {
+			if let Some((k, v)) = slot {
+				if !f(k, v) {
+					*slot = None;
+				}
+			}
+		}
+	}
+
+	// most entries the cache will ever hold
+	pub fn capacity(&self) -> usize {
+		self.max_slots
+	}
+
+	pub fn stats(&self) -> CacheStats {
+		self.stats
+	}
+
+	pub fn reset_stats(&mut self) {
+		self.stats = CacheStats::default();
+	}
+
+	fn slot(&self, key: &K) -> usize {
+		let mut hasher = DefaultHasher::new();
+		key.hash(&mut hasher);
+		hasher.finish() as usize & (self.slots.len() - 1)
+	}
+
+	// double the slots, entries landing on the same slot keep the later one
+	fn grow(&mut self) {
+		let len = 2 * self.slots.len();
+		let old = std::mem::replace(&mut self.slots, (0..len).map(|_| None).collect());
+		for (k, v) in old.into_iter().flatten() {
+			let i = self.slot(&k);
+			self.slots[i] = Some((k, v));
+		}
+		self.fills = 0;
+	}
+}
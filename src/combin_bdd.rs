@@ -1,37 +1,47 @@
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use crate::cache::*;
 use crate::expr::*;
 use crate::neg_arc_bdd::*;
 use Expr::*;
 use BOp::*;
 
+// Computed table of the and constructions, bounded and lossy
+pub type AndCache = ComputedCache<(FunctionNode, FunctionNode), FunctionNode>;
+
 /////////////////////////
 pub fn from_combinatorial(e: &Expr, var_ord: &[usize]) -> Bdd {
+	from_combinatorial_cached(e, var_ord, &mut AndCache::new(DEFAULT_CACHE_SLOTS))
+}
+
+// from_combinatorial with a caller sized computed table, whose stats are
+// left for the caller to read
+pub fn from_combinatorial_cached(e: &Expr, var_ord: &[usize], cache: &mut AndCache) -> Bdd {
 	fn rec(e: &Expr,
-		   var_ord: &[usize],
+		   levels: &LevelMap,
            nodes: &mut Vec<InternalNode>,
            unique_table: &mut BTreeMap<InternalNode, NodeIdx>,
-           computed_table: &mut BTreeMap<(FunctionNode, FunctionNode), FunctionNode>) -> FunctionNode {
+           computed_table: &mut AndCache) -> FunctionNode {
 		match e {
 			Lit(b) => func(term, !b),
 			Var(x) => unique_insert_btree(*x, func(term, false), func(term, true), nodes, unique_table),
 			Not(e1) => {
-				let mut f = rec(e1, var_ord, nodes, unique_table, computed_table);
+				let mut f = rec(e1, levels, nodes, unique_table, computed_table);
 				f.complement = !f.complement;
 				f
 			},
 			Binary(e1, And, e2) => {
-				let f1 = rec(e1, var_ord, nodes, unique_table, computed_table);
-				let f2 = rec(e2, var_ord, nodes, unique_table, computed_table);
-				bdd_and(f1, f2, var_ord, nodes, unique_table, computed_table)
+				let f1 = rec(e1, levels, nodes, unique_table, computed_table);
+				let f2 = rec(e2, levels, nodes, unique_table, computed_table);
+				bdd_and(f1, f2, levels, nodes, unique_table, computed_table)
 			},
 			Binary(e1, Or , e2) => {
 				// apply and using demorgan
-				let mut f1 = rec(e1, var_ord, nodes, unique_table, computed_table);
+				let mut f1 = rec(e1, levels, nodes, unique_table, computed_table);
 				f1.complement = !f1.complement;
-				let mut f2 = rec(e2, var_ord, nodes, unique_table, computed_table);
+				let mut f2 = rec(e2, levels, nodes, unique_table, computed_table);
 				f2.complement = !f2.complement;
-				let mut f = bdd_and(f1, f2, var_ord, nodes, unique_table, computed_table);
+				let mut f = bdd_and(f1, f2, levels, nodes, unique_table, computed_table);
 				f.complement = !f.complement;
 				f
 			},
@@ -41,9 +51,10 @@ pub fn from_combinatorial(e: &Expr, var_ord: &[usize]) -> Bdd {
 	let mut bdd = Bdd {
 		f: func(0, false),
 		nodes: Vec::new(),
+		var_ord: var_ord.to_vec(),
 	};
 	// let mut cof_asgn = vec![false; var_ord.len()];
-	bdd.f = rec(e, var_ord, &mut bdd.nodes, &mut BTreeMap::new(), &mut BTreeMap::new());
+	bdd.f = rec(e, &LevelMap::new(var_ord), &mut bdd.nodes, &mut BTreeMap::new(), cache);
 	bdd
 }
 
@@ -53,10 +64,10 @@ pub fn from_combinatorial(e: &Expr, var_ord: &[usize]) -> Bdd {
 
 fn bdd_and(f: FunctionNode,
 	       g: FunctionNode,
-	       rem_support: &[usize],
+	       levels: &LevelMap,
 	       nodes: &mut Vec<InternalNode>,
 	       unique_table: &mut BTreeMap<InternalNode, NodeIdx>,
-	       computed_table: &mut BTreeMap<(FunctionNode, FunctionNode), FunctionNode>) -> FunctionNode {
+	       computed_table: &mut AndCache) -> FunctionNode {
 	// if (terminal case) return result
 	if f.head == g.head {
 		// f == g || f == !g
@@ -73,7 +84,7 @@ fn bdd_and(f: FunctionNode,
 		// if (computed table has entry ({f, g}, r)) return r;
 		let entry_key = if f < g { (f, g) } else { (g, f) };
 		match computed_table.get(&entry_key) {
-			Some(r) => *r,
+			Some(r) => r,
 			None    => {
 		
 				let coff = |f: FunctionNode, x: usize| -> (FunctionNode, FunctionNode) {
@@ -86,15 +97,16 @@ fn bdd_and(f: FunctionNode,
 				};
 
 				// let x be the top variable of {f,g};
-				let x = rem_support[0];
+				let lvl = levels.node_level(f.head, nodes).min(levels.node_level(g.head, nodes));
+				let x = levels.var(lvl);
 				let (f_x, f_nx) = coff(f, x);
 				let (g_x, g_nx) = coff(g, x);
 				
 				// t = AND(fx, gx);
-				let t = bdd_and(f_x, g_x, &rem_support[1..], nodes, unique_table, computed_table);
+				let t = bdd_and(f_x, g_x, levels, nodes, unique_table, computed_table);
 				
 				// e = AND(f¬x, g¬x);
-				let e = bdd_and(f_nx, g_nx, &rem_support[1..], nodes, unique_table, computed_table);
+				let e = bdd_and(f_nx, g_nx, levels, nodes, unique_table, computed_table);
 
 				// r = findOrAddUniqueTable(x, t, e);
 				let r = unique_insert_btree(x, t, e, nodes, unique_table);
@@ -113,31 +125,37 @@ fn bdd_and(f: FunctionNode,
 /////////////////////////////////
 
 pub fn from_combinatorial_hash(e: &Expr, var_ord: &[usize]) -> Bdd {
+	from_combinatorial_hash_cached(e, var_ord, &mut AndCache::new(DEFAULT_CACHE_SLOTS))
+}
+
+// from_combinatorial_hash with a caller sized computed table, whose stats
+// are left for the caller to read
+pub fn from_combinatorial_hash_cached(e: &Expr, var_ord: &[usize], cache: &mut AndCache) -> Bdd {
 	fn rec(e: &Expr,
-		   var_ord: &[usize],
+		   levels: &LevelMap,
            nodes: &mut Vec<InternalNode>,
            unique_table: &mut HashMap<InternalNode, NodeIdx>,
-           computed_table: &mut HashMap<(FunctionNode, FunctionNode), FunctionNode>) -> FunctionNode {
+           computed_table: &mut AndCache) -> FunctionNode {
 		match e {
 			Lit(b) => func(term, !b),
 			Var(x) => unique_insert_hash(*x, func(term, false), func(term, true), nodes, unique_table),
 			Not(e1) => {
-				let mut f = rec(e1, var_ord, nodes, unique_table, computed_table);
+				let mut f = rec(e1, levels, nodes, unique_table, computed_table);
 				f.complement = !f.complement;
 				f
 			},
 			Binary(e1, And, e2) => {
-				let f1 = rec(e1, var_ord, nodes, unique_table, computed_table);
-				let f2 = rec(e2, var_ord, nodes, unique_table, computed_table);
-				bdd_and_hash(f1, f2, var_ord, nodes, unique_table, computed_table)
+				let f1 = rec(e1, levels, nodes, unique_table, computed_table);
+				let f2 = rec(e2, levels, nodes, unique_table, computed_table);
+				bdd_and_hash(f1, f2, levels, nodes, unique_table, computed_table)
 			},
 			Binary(e1, Or , e2) => {
 				// apply and using demorgan
-				let mut f1 = rec(e1, var_ord, nodes, unique_table, computed_table);
+				let mut f1 = rec(e1, levels, nodes, unique_table, computed_table);
 				f1.complement = !f1.complement;
-				let mut f2 = rec(e2, var_ord, nodes, unique_table, computed_table);
+				let mut f2 = rec(e2, levels, nodes, unique_table, computed_table);
 				f2.complement = !f2.complement;
-				let mut f = bdd_and_hash(f1, f2, var_ord, nodes, unique_table, computed_table);
+				let mut f = bdd_and_hash(f1, f2, levels, nodes, unique_table, computed_table);
 				f.complement = !f.complement;
 				f
 			},
@@ -147,18 +165,19 @@ pub fn from_combinatorial_hash(e: &Expr, var_ord: &[usize]) -> Bdd {
 	let mut bdd = Bdd {
 		f: func(0, false),
 		nodes: Vec::new(),
+		var_ord: var_ord.to_vec(),
 	};
 	// let mut cof_asgn = vec![false; var_ord.len()];
-	bdd.f = rec(e, var_ord, &mut bdd.nodes, &mut HashMap::new(), &mut HashMap::new());
+	bdd.f = rec(e, &LevelMap::new(var_ord), &mut bdd.nodes, &mut HashMap::new(), cache);
 	bdd
 }
 
 fn bdd_and_hash(f: FunctionNode,
 	       g: FunctionNode,
-	       rem_support: &[usize],
+	       levels: &LevelMap,
 	       nodes: &mut Vec<InternalNode>,
 	       unique_table: &mut HashMap<InternalNode, NodeIdx>,
-	       computed_table: &mut HashMap<(FunctionNode, FunctionNode), FunctionNode>) -> FunctionNode {
+	       computed_table: &mut AndCache) -> FunctionNode {
 	// if (terminal case) return result
 	if f.head == g.head {
 		// f == g || f == !g
@@ -175,7 +194,7 @@ fn bdd_and_hash(f: FunctionNode,
 		// if (computed table has entry ({f, g}, r)) return r;
 		let entry_key = if f < g { (f, g) } else { (g, f) };
 		match computed_table.get(&entry_key) {
-			Some(r) => *r,
+			Some(r) => r,
 			None    => {
 		
 				let coff = |f: FunctionNode, x: usize| -> (FunctionNode, FunctionNode) {
@@ -188,15 +207,16 @@ fn bdd_and_hash(f: FunctionNode,
 				};
 
 				// let x be the top variable of {f,g};
-				let x = rem_support[0];
+				let lvl = levels.node_level(f.head, nodes).min(levels.node_level(g.head, nodes));
+				let x = levels.var(lvl);
 				let (f_x, f_nx) = coff(f, x);
 				let (g_x, g_nx) = coff(g, x);
 				
 				// t = AND(fx, gx);
-				let t = bdd_and_hash(f_x, g_x, &rem_support[1..], nodes, unique_table, computed_table);
+				let t = bdd_and_hash(f_x, g_x, levels, nodes, unique_table, computed_table);
 				
 				// e = AND(f¬x, g¬x);
-				let e = bdd_and_hash(f_nx, g_nx, &rem_support[1..], nodes, unique_table, computed_table);
+				let e = bdd_and_hash(f_nx, g_nx, levels, nodes, unique_table, computed_table);
 
 				// r = findOrAddUniqueTable(x, t, e);
 				let r = unique_insert_hash(x, t, e, nodes, unique_table);
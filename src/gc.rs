@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use crate::manager::Manager;
+use crate::neg_arc_bdd::*;
+use crate::unique::UniqueTable;
+
+// Garbage collection.
+// Protected functions are the roots. Nodes they can't reach are forgotten by
+// the unique and computed tables and their slots go on the free list, so any
+// unprotected handle may be left pointing at a reused slot.
+impl Manager {
+
+	// free every node unreachable from a protected function, returns how many
+	// were freed by this collection
+	pub fn gc(&mut self) -> usize {
+		let live = self.mark(&[]);
+		self.sweep(&live)
+	}
+
+	// Collect, then renumber the live nodes children first so the arena has
+	// no holes left. Every node moves, so each handle still in use must be
+	// passed in handles to be rewritten in place, protected or not. handles
+	// also count as roots, and protections move along with their nodes.
+	pub fn compact(&mut self, handles: &mut [FunctionNode]) {
+		let live = self.mark(handles);
+		self.sweep(&live);
+
+		let mut moved = vec![term; self.nodes.len()];
+		let mut nodes = Vec::with_capacity(self.nodes.len() - self.free.len());
+		let roots: Vec<NodeIdx> = self.protected.keys().cloned()
+			.chain(handles.iter().map(|f| f.head))
+			.collect();
+		for root in roots {
+			self.compact_rec(root, &mut nodes, &mut moved);
+		}
+		let relocate = |f: FunctionNode| {
+			if f.head == term { f } else { func(moved[f.head], f.complement) }
+		};
+
+		for f in handles.iter_mut() {
+			*f = relocate(*f);
+		}
+		self.protected = self.protected.iter()
+			.map(|(&n, &count)| (moved[n], count))
+			.collect();
+		// results are cheap to lose, not worth relocating
+		self.computed_table.clear();
+		self.unique_table = UniqueTable::new(self.level_map.num_vars());
+		for (i, node) in nodes.iter().enumerate() {
+			self.unique_table.insert(node, i);
+		}
+		self.nodes = nodes;
+		self.free.clear();
+	}
+
+	// which slots are reachable from the protected functions and extra roots
+	fn mark(&self, extra: &[FunctionNode]) -> Vec<bool> {
+		let mut live = vec![false; self.nodes.len()];
+		let mut stack: Vec<NodeIdx> = self.protected.keys().cloned()
+			.chain(extra.iter().map(|f| f.head))
+			.collect();
+		while let Some(n) = stack.pop() {
+			if n != term && !live[n] {
+				live[n] = true;
+				stack.push(self.nodes[n].t_arc);
+				stack.push(self.nodes[n].e_arc);
+			}
+		}
+		live
+	}
+
+	// drop table entries that mention dead nodes and free their slots
+	fn sweep(&mut self, live: &[bool]) -> usize {
+		let is_live = |f: &FunctionNode| f.head == term || live[f.head];
+		self.unique_table.retain(|n| live[n]);
+		self.computed_table.retain(|(_, f, g, h), r| {
+			is_live(f) && is_live(g) && is_live(h) && is_live(r)
+		});
+		let already_free = self.free.len();
+		// lowest slot last, so the arena refills from the bottom
+		self.free = (0..self.nodes.len()).rev().filter(|&n| !live[n]).collect();
+		self.free.len() - already_free
+	}
+
+	fn compact_rec(&self, n: NodeIdx, nodes: &mut Vec<InternalNode>, moved: &mut Vec<NodeIdx>) -> NodeIdx {
+		if n == term {
+			return term;
+		}
+		if moved[n] != term {
+			return moved[n];
+		}
+		let node = &self.nodes[n];
+		let t_arc = self.compact_rec(node.t_arc, nodes, moved);
+		let e_arc = self.compact_rec(node.e_arc, nodes, moved);
+		nodes.push(InternalNode {
+			label: node.label,
+			t_arc,
+			e_arc,
+			e_complement: node.e_complement,
+		});
+		moved[n] = nodes.len() - 1;
+		moved[n]
+	}
+}
+
+impl Bdd {
+
+	// Drop the nodes f can't reach, e.g. the intermediate results the
+	// combinatorial constructors leave behind, children first
+	pub fn compact(&mut self) {
+		fn rec(old: &[InternalNode], n: NodeIdx, nodes: &mut Vec<InternalNode>, moved: &mut HashMap<NodeIdx, NodeIdx>) -> NodeIdx {
+			if n == term {
+				return term;
+			}
+			if let Some(&i) = moved.get(&n) {
+				return i;
+			}
+			let node = &old[n];
+			let t_arc = rec(old, node.t_arc, nodes, moved);
+			let e_arc = rec(old, node.e_arc, nodes, moved);
+			nodes.push(InternalNode {
+				label: node.label,
+				t_arc,
+				e_arc,
+				e_complement: node.e_complement,
+			});
+			moved.insert(n, nodes.len() - 1);
+			nodes.len() - 1
+		}
+
+		let mut nodes = Vec::new();
+		self.f.head = rec(&self.nodes, self.f.head, &mut nodes, &mut HashMap::new());
+		self.nodes = nodes;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::expr::*;
+	use crate::manager::Manager;
+	use crate::test_util::*;
+	use Expr::*;
+	use BOp::*;
+
+	#[test]
+	fn gc_keeps_protected() {
+		let mut es = exprs();
+		let mut m = Manager::new(&bad_order());
+		let mut fs = build(&mut m, &es);
+		assert!(m.gc() > 0);
+		check(&m, &es, &fs);
+
+		// dropping a root frees whatever only it reached
+		m.unprotect(fs.pop().unwrap());
+		es.pop();
+		assert!(m.gc() > 0);
+		check(&m, &es, &fs);
+		assert_eq!(m.gc(), 0);
+	}
+
+	#[test]
+	fn freed_slots_are_reused() {
+		let mut es = exprs();
+		let mut m = Manager::new(&bad_order());
+		let mut fs = build(&mut m, &es);
+		m.unprotect(fs.pop().unwrap());
+		es.pop();
+		m.gc();
+		let slots = m.node_count();
+
+		let e = bin(Var(5), Or, not(Var(2)));
+		es.push(e);
+		fs.extend(build(&mut m, &es[es.len() - 1..]));
+		assert_eq!(m.node_count(), slots);
+		m.gc();
+		check(&m, &es, &fs);
+	}
+
+	#[test]
+	fn compact_renumbers_handles() {
+		let es = exprs();
+		let mut m = Manager::new(&bad_order());
+		let mut fs = build(&mut m, &es);
+		// an unprotected handle survives too when passed in
+		let e = bin(Var(2), And, Var(3));
+		let g = m.from_expr(&e);
+		fs.push(g);
+		m.compact(&mut fs);
+		assert_eq!(m.node_count(), m.live_node_count());
+
+		let mut es = es;
+		es.push(e);
+		m.protect(fs[fs.len() - 1]);
+		check(&m, &es, &fs);
+
+		// protections moved along with their nodes
+		assert_eq!(m.gc(), 0);
+		let e = bin(Var(0), And, not(Var(1)));
+		es.push(e);
+		fs.extend(build(&mut m, &es[es.len() - 1..]));
+		m.gc();
+		check(&m, &es, &fs);
+	}
+}
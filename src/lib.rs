@@ -3,3 +3,16 @@ pub mod naive_bdd;
 pub mod neg_arc_bdd;
 pub mod combin_bdd;
 pub mod expr_rc;
+pub mod manager;
+pub mod big_uint;
+pub mod sat;
+pub mod rng;
+pub mod reorder;
+pub mod order;
+pub mod gc;
+pub mod cache;
+pub mod unique;
+pub mod packed_bdd;
+pub mod par_bdd;
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,1024 @@
+use std::collections::HashMap;
+
+use crate::big_uint::BigUint;
+use crate::cache::*;
+use crate::expr::*;
+use crate::neg_arc_bdd::*;
+use crate::unique::UniqueTable;
+use Expr::*;
+use BOp::*;
+
+// Operations with their own entries in the computed table
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Op {
+	Ite,
+	Exists,
+	Unique,
+	AndExists,
+	Compose,
+	Constrain,
+	RestrictCare,
+}
+
+// Long lived owner of a node arena shared by many functions.
+// Functions are handed out as FunctionNodes pointing into `nodes`, so any
+// number of roots can share sub-graphs, and the unique and computed tables
+// are kept around between constructions instead of being thrown away.
+#[derive(Debug)]
+pub struct Manager {
+	// list of nodes, shared by every function built in this manager
+	pub nodes: Vec<InternalNode>,
+	pub(crate) level_map: LevelMap,
+	// one subtable per var
+	pub(crate) unique_table: UniqueTable,
+	pub(crate) computed_table: ComputedCache<(Op, FunctionNode, FunctionNode, FunctionNode), FunctionNode>,
+	// roots kept alive across reordering, with how many times each was protected
+	pub(crate) protected: HashMap<NodeIdx, usize>,
+	// reorder once the unique table grows past this many nodes
+	pub(crate) auto_reorder: Option<usize>,
+	// vars kept contiguous by reordering
+	pub(crate) groups: Vec<Vec<usize>>,
+	// slots of collected nodes, reused before the arena grows
+	pub(crate) free: Vec<NodeIdx>,
+	op_depth: usize,
+}
+
+impl Manager {
+
+	pub fn new(var_ord: &[usize]) -> Manager {
+		Manager {
+			nodes: Vec::new(),
+			level_map: LevelMap::new(var_ord),
+			unique_table: UniqueTable::new(var_ord.len()),
+			computed_table: ComputedCache::new(DEFAULT_CACHE_SLOTS),
+			protected: HashMap::new(),
+			auto_reorder: None,
+			groups: Vec::new(),
+			free: Vec::new(),
+			op_depth: 0,
+		}
+	}
+
+	pub fn var_ord(&self) -> &[usize] {
+		&self.level_map.invperm
+	}
+
+	pub fn num_vars(&self) -> usize {
+		self.level_map.num_vars()
+	}
+
+	// number of slots in the arena, live or collected, not including terminal
+	pub fn node_count(&self) -> usize {
+		self.nodes.len()
+	}
+
+	// bound the computed table to max_slots entries, forgetting what it holds
+	pub fn set_cache_size(&mut self, max_slots: usize) {
+		self.computed_table = ComputedCache::new(max_slots);
+	}
+
+	// live nodes at each level, root level first
+	pub fn level_sizes(&self) -> Vec<usize> {
+		self.level_map.invperm.iter().map(|&x| self.unique_table.var_len(x)).collect()
+	}
+
+	pub fn cache_stats(&self) -> CacheStats {
+		self.computed_table.stats()
+	}
+
+	pub fn constant(&self, b: bool) -> FunctionNode {
+		// unit terminal is true, so false representation requires complementation
+		func(term, !b)
+	}
+
+	pub fn var(&mut self, x: usize) -> FunctionNode {
+		self.run_op(|m| m.unique_insert(x, func(term, false), func(term, true)))
+	}
+
+	pub fn not(&self, f: FunctionNode) -> FunctionNode {
+		func(f.head, !f.complement)
+	}
+
+	// if f then g else h, every binary operator is expressed through it
+	pub fn ite(&mut self, f: FunctionNode, g: FunctionNode, h: FunctionNode) -> FunctionNode {
+		self.run_op(|m| m.ite_rec(f, g, h))
+	}
+
+	pub fn and(&mut self, f: FunctionNode, g: FunctionNode) -> FunctionNode {
+		self.ite(f, g, func(term, true))
+	}
+
+	pub fn or(&mut self, f: FunctionNode, g: FunctionNode) -> FunctionNode {
+		self.ite(f, func(term, false), g)
+	}
+
+	pub fn xor(&mut self, f: FunctionNode, g: FunctionNode) -> FunctionNode {
+		let ng = self.not(g);
+		self.ite(f, ng, g)
+	}
+
+	pub fn xnor(&mut self, f: FunctionNode, g: FunctionNode) -> FunctionNode {
+		let ng = self.not(g);
+		self.ite(f, g, ng)
+	}
+
+	pub fn nand(&mut self, f: FunctionNode, g: FunctionNode) -> FunctionNode {
+		let r = self.and(f, g);
+		self.not(r)
+	}
+
+	pub fn nor(&mut self, f: FunctionNode, g: FunctionNode) -> FunctionNode {
+		let r = self.or(f, g);
+		self.not(r)
+	}
+
+	pub fn implies(&mut self, f: FunctionNode, g: FunctionNode) -> FunctionNode {
+		self.ite(f, g, func(term, false))
+	}
+
+	// conjunction of the positive literals of vars, used to name a set of
+	// variables for quantification
+	pub fn cube(&mut self, vars: &[usize]) -> FunctionNode {
+		self.run_op(|m| {
+			let mut vars = vars.to_vec();
+			// build from the bottom level up
+			vars.sort_by_key(|&x| std::cmp::Reverse(m.level_map.level(x)));
+			vars.dedup();
+			let mut cube = func(term, false);
+			for x in vars {
+				cube = m.unique_insert(x, cube, func(term, true));
+			}
+			cube
+		})
+	}
+
+	// exists x in cube. f
+	pub fn exists(&mut self, f: FunctionNode, cube: FunctionNode) -> FunctionNode {
+		self.run_op(|m| m.quant_rec(f, cube, Op::Exists))
+	}
+
+	// forall x in cube. f == !(exists x in cube. !f)
+	pub fn forall(&mut self, f: FunctionNode, cube: FunctionNode) -> FunctionNode {
+		let nf = self.not(f);
+		let r = self.exists(nf, cube);
+		self.not(r)
+	}
+
+	// unique x in cube. f, the boolean difference of f over the cube
+	pub fn unique(&mut self, f: FunctionNode, cube: FunctionNode) -> FunctionNode {
+		self.run_op(|m| m.quant_rec(f, cube, Op::Unique))
+	}
+
+	// exists x in cube. f && g, without building f && g first
+	pub fn and_exists(&mut self, f: FunctionNode, g: FunctionNode, cube: FunctionNode) -> FunctionNode {
+		self.run_op(|m| m.and_exists_rec(f, g, cube))
+	}
+
+	// f with var x replaced by g
+	pub fn compose(&mut self, f: FunctionNode, x: usize, g: FunctionNode) -> FunctionNode {
+		self.run_op(|m| {
+			let x_f = m.var(x);
+			m.compose_rec(f, x_f, g)
+		})
+	}
+
+	// f with every var in subst simultaneously replaced by its function
+	pub fn vector_compose(&mut self, f: FunctionNode, subst: &HashMap<usize, FunctionNode>) -> FunctionNode {
+		// nodes below the deepest substituted var are left as is
+		let max_lvl = subst.keys().map(|&x| self.level_map.level(x)).max();
+		match max_lvl {
+			Some(max_lvl) => self.run_op(|m| {
+				let r = m.vector_compose_rec(f.head, subst, max_lvl, &mut HashMap::new());
+				if f.complement { m.not(r) } else { r }
+			}),
+			None => f,
+		}
+	}
+
+	// relabel every var x of f as perm[x]
+	pub fn permute(&mut self, f: FunctionNode, perm: &[usize]) -> FunctionNode {
+		let map = perm.iter().cloned().enumerate().collect();
+		self.rename(f, &map)
+	}
+
+	// relabel the vars of f found in map, others keep their label
+	pub fn rename(&mut self, f: FunctionNode, map: &HashMap<usize, usize>) -> FunctionNode {
+		let rename = |x: usize| *map.get(&x).unwrap_or(&x);
+		let support = self.support(f);
+		let keeps_ord = support.windows(2)
+			.all(|w| self.level_map.level(rename(w[0])) < self.level_map.level(rename(w[1])));
+		self.run_op(|m| {
+			if keeps_ord {
+				// nodes stay in the same relative order, just swap the labels
+				let r = m.relabel_rec(f.head, &rename, &mut HashMap::new());
+				if f.complement { m.not(r) } else { r }
+			} else {
+				// labels move past each other, rebuild in this manager's order
+				let subst = support.iter()
+					.map(|&x| (x, m.var(rename(x))))
+					.collect();
+				m.vector_compose(f, &subst)
+			}
+		})
+	}
+
+	// vars f depends on, from the root level down
+	pub fn support(&self, f: FunctionNode) -> Vec<usize> {
+		let mut visited = vec![false; self.nodes.len()];
+		let mut in_support = vec![false; self.level_map.num_vars()];
+		let mut stack = vec![f.head];
+		while let Some(n) = stack.pop() {
+			if n != term && !visited[n] {
+				visited[n] = true;
+				in_support[self.nodes[n].label] = true;
+				stack.push(self.nodes[n].t_arc);
+				stack.push(self.nodes[n].e_arc);
+			}
+		}
+		self.level_map.invperm.iter().cloned().filter(|&x| in_support[x]).collect()
+	}
+
+	// generalized cofactor of f by care set c, agrees with f wherever c holds
+	pub fn constrain(&mut self, f: FunctionNode, c: FunctionNode) -> FunctionNode {
+		self.run_op(|m| m.constrain_rec(f, c))
+	}
+
+	// Coudert-Madre restrict of f by care set c, agrees with f wherever c
+	// holds and never depends on vars f does not depend on
+	pub fn restrict_care(&mut self, f: FunctionNode, c: FunctionNode) -> FunctionNode {
+		self.run_op(|m| m.restrict_care_rec(f, c))
+	}
+
+	pub fn from_expr(&mut self, e: &Expr) -> FunctionNode {
+		self.run_op(|m| m.expr_rec(e))
+	}
+
+	fn expr_rec(&mut self, e: &Expr) -> FunctionNode {
+		match e {
+			Lit(b) => self.constant(*b),
+			Var(x) => self.var(*x),
+			Not(e1) => {
+				let f = self.expr_rec(e1);
+				self.not(f)
+			},
+			Binary(e1, And, e2) => {
+				let f1 = self.expr_rec(e1);
+				let f2 = self.expr_rec(e2);
+				self.and(f1, f2)
+			},
+			Binary(e1, Or , e2) => {
+				let f1 = self.expr_rec(e1);
+				let f2 = self.expr_rec(e2);
+				self.or(f1, f2)
+			},
+		}
+	}
+
+	// Copy a standalone bdd into the shared arena.
+	// Bdds built with the same order are relinked node by node, anything else
+	// is rebuilt through apply so it ends up in this manager's order.
+	pub fn import(&mut self, bdd: &Bdd) -> FunctionNode {
+		let same_ord = bdd.var_ord == self.level_map.invperm;
+		self.run_op(|m| {
+			let f = m.import_rec(bdd, bdd.f.head, same_ord, &mut HashMap::new());
+			if bdd.f.complement { m.not(f) } else { f }
+		})
+	}
+
+	fn import_rec(&mut self, bdd: &Bdd, n: NodeIdx, same_ord: bool, imported: &mut HashMap<NodeIdx, FunctionNode>) -> FunctionNode {
+		if n == term {
+			return func(term, false);
+		}
+		match imported.get(&n) {
+			Some(f) => *f,
+			None => {
+				let node = &bdd.nodes[n];
+				let t = self.import_rec(bdd, node.t_arc, same_ord, imported);
+				let mut e = self.import_rec(bdd, node.e_arc, same_ord, imported);
+				if node.e_complement {
+					e = self.not(e);
+				}
+				let f = if same_ord {
+					self.unique_insert(node.label, t, e)
+				} else {
+					// x t + !x e
+					let x = self.var(node.label);
+					let pos = self.and(x, t);
+					let nx = self.not(x);
+					let neg = self.and(nx, e);
+					self.or(pos, neg)
+				};
+				imported.insert(n, f);
+				f
+			}
+		}
+	}
+
+	// Copy the nodes reachable from f out into a standalone bdd
+	pub fn export(&self, f: FunctionNode) -> Bdd {
+		let mut bdd = Bdd {
+			f: func(term, f.complement),
+			nodes: Vec::new(),
+			var_ord: self.level_map.invperm.clone(),
+		};
+		let mut exported = HashMap::new();
+		bdd.f.head = self.export_rec(f.head, &mut bdd.nodes, &mut exported);
+		bdd
+	}
+
+	fn export_rec(&self, n: NodeIdx, nodes: &mut Vec<InternalNode>, exported: &mut HashMap<NodeIdx, NodeIdx>) -> NodeIdx {
+		if n == term {
+			return term;
+		}
+		match exported.get(&n) {
+			Some(i) => *i,
+			None => {
+				let node = &self.nodes[n];
+				// children first, same layout the constructors produce
+				let t_arc = self.export_rec(node.t_arc, nodes, exported);
+				let e_arc = self.export_rec(node.e_arc, nodes, exported);
+				let i = nodes.len();
+				nodes.push(InternalNode {
+					label: node.label,
+					t_arc,
+					e_arc,
+					e_complement: node.e_complement,
+				});
+				exported.insert(n, i);
+				i
+			}
+		}
+	}
+
+	pub fn eval(&self, f: FunctionNode, env: &Env) -> bool {
+		let mut next_node = f.head;
+		let mut complement = f.complement;
+		while next_node != term {
+			let node = &self.nodes[next_node];
+			next_node = if env[node.label] {
+				node.t_arc
+			} else {
+				complement ^= node.e_complement;
+				node.e_arc
+			};
+		}
+		// true if complement is false
+		!complement
+	}
+
+	// size of bdd reachable from f, not including terminal
+	pub fn size(&self, f: FunctionNode) -> usize {
+		self.shared_size(&[f])
+	}
+
+	// number of distinct nodes reachable from any of fs, not including terminal
+	pub fn shared_size(&self, fs: &[FunctionNode]) -> usize {
+		let mut visited = vec![false; self.nodes.len()];
+		let mut stack: Vec<NodeIdx> = fs.iter().map(|f| f.head).collect();
+		let mut count = 0;
+		while let Some(n) = stack.pop() {
+			if n != term && !visited[n] {
+				visited[n] = true;
+				count += 1;
+				stack.push(self.nodes[n].t_arc);
+				stack.push(self.nodes[n].e_arc);
+			}
+		}
+		count
+	}
+
+	// number of assignments to num_vars vars satisfying f, num_vars can't be
+	// less than the vars of the manager
+	pub fn sat_count(&self, f: FunctionNode, num_vars: usize) -> BigUint {
+		self.export(f).sat_count(num_vars)
+	}
+
+	// probability f is true when each var x is independently true with
+	// probability p[x]
+	pub fn probability(&self, f: FunctionNode, p: &[f64]) -> f64 {
+		self.export(f).probability(p)
+	}
+
+	// cheapest satisfying assignment of f and its cost, costs[x] is
+	// (cost of x, cost of !x)
+	pub fn min_cost_sat(&self, f: FunctionNode, costs: &[(f64, f64)]) -> Option<(Env, f64)> {
+		self.export(f).min_cost_sat(costs)
+	}
+
+	// one satisfying cube of f, vars left out are don't cares
+	pub fn any_sat(&self, f: FunctionNode) -> Option<PartialEnv> {
+		self.export(f).any_sat()
+	}
+
+	pub fn textual_repr(&self, f: FunctionNode) -> String {
+		self.export(f).textual_repr()
+	}
+
+	// Every public operation that builds nodes runs through here. Automatic
+	// reordering waits until the outermost operation is done, so partial
+	// results held on the stack of a recursion are never moved.
+	fn run_op<F>(&mut self, op: F) -> FunctionNode
+		where F: FnOnce(&mut Manager) -> FunctionNode {
+		self.op_depth += 1;
+		let r = op(self);
+		self.op_depth -= 1;
+		if self.op_depth == 0 {
+			self.maybe_reorder(r);
+		}
+		r
+	}
+
+	// Same normalization as unique_insert_hash, but new nodes take a collected
+	// slot when there is one
+	pub(crate) fn unique_insert(&mut self, x: usize, pos_cof: FunctionNode, neg_cof: FunctionNode) -> FunctionNode {
+		if pos_cof == neg_cof {
+			return pos_cof;
+		}
+		// then arc kept regular, a negated pos_cof moves up to the function
+		let complement = pos_cof.complement;
+		let node = InternalNode {
+			label: x,
+			t_arc: pos_cof.head,
+			e_arc: neg_cof.head,
+			e_complement: neg_cof.complement ^ complement,
+		};
+		if let Some(i) = self.unique_table.get(&node) {
+			return func(i, complement);
+		}
+		let i = match self.free.pop() {
+			Some(i) => {
+				self.nodes[i] = node.clone();
+				i
+			},
+			None => {
+				self.nodes.push(node.clone());
+				self.nodes.len() - 1
+			},
+		};
+		self.unique_table.insert(&node, i);
+		func(i, complement)
+	}
+
+	// level of the top variable of f, terminal sits below every variable
+	pub(crate) fn level(&self, f: FunctionNode) -> usize {
+		self.level_map.node_level(f.head, &self.nodes)
+	}
+
+	// positive and negative cofactors of f with respect to the var at lvl
+	fn cofactors(&self, f: FunctionNode, lvl: usize) -> (FunctionNode, FunctionNode) {
+		if self.level(f) == lvl {
+			let f_node = &self.nodes[f.head];
+			(func(f_node.t_arc, f.complement), func(f_node.e_arc, f_node.e_complement ^ f.complement))
+		} else {
+			(f, f)
+		}
+	}
+
+	fn ite_rec(&mut self, f: FunctionNode, g: FunctionNode, h: FunctionNode) -> FunctionNode {
+		let one = func(term, false);
+		let zero = func(term, true);
+
+		// if (terminal case) return result
+		if f == one {
+			return g;
+		} else if f == zero {
+			return h;
+		}
+
+		// ite(f, f, h) == ite(f, 1, h), ite(f, !f, h) == ite(f, 0, h)
+		let g = if g.head == f.head { func(term, g.complement != f.complement) } else { g };
+		// ite(f, g, f) == ite(f, g, 0), ite(f, g, !f) == ite(f, g, 1)
+		let h = if h.head == f.head { func(term, h.complement == f.complement) } else { h };
+
+		if g == h {
+			return g;
+		} else if g == one && h == zero {
+			return f;
+		} else if g == zero && h == one {
+			return self.not(f);
+		}
+
+		// standard triples: of the equivalent argument orders, keep the
+		// one with the smaller first argument
+		let (mut f, mut g, mut h) = (f, g, h);
+		if g == one {
+			// ite(f, 1, h) == ite(h, 1, f)
+			if h.head < f.head { std::mem::swap(&mut f, &mut h); }
+		} else if g == zero {
+			// ite(f, 0, h) == ite(!h, 0, !f)
+			if h.head < f.head {
+				let (nf, nh) = (self.not(h), self.not(f));
+				f = nf;
+				h = nh;
+			}
+		} else if h == zero {
+			// ite(f, g, 0) == ite(g, f, 0)
+			if g.head < f.head { std::mem::swap(&mut f, &mut g); }
+		} else if h == one {
+			// ite(f, g, 1) == ite(!g, !f, 1)
+			if g.head < f.head {
+				let (nf, ng) = (self.not(g), self.not(f));
+				f = nf;
+				g = ng;
+			}
+		} else if g.head == h.head && g.head < f.head {
+			// ite(f, g, !g) == ite(g, f, !f)
+			let nf = self.not(f);
+			h = nf;
+			std::mem::swap(&mut f, &mut g);
+		}
+
+		// complement normalization: f and g regular, pushing any
+		// complement on g out to the result
+		if f.complement {
+			// ite(!f, g, h) == ite(f, h, g)
+			f = self.not(f);
+			std::mem::swap(&mut g, &mut h);
+		}
+		let complement = g.complement;
+		if complement {
+			// ite(f, !g, h) == !ite(f, g, !h)
+			g = self.not(g);
+			h = self.not(h);
+		}
+
+		// if (computed table has entry ({f, g, h}, r)) return r;
+		let entry_key = (Op::Ite, f, g, h);
+		let r = match self.computed_table.get(&entry_key) {
+			Some(r) => r,
+			None => {
+				// let x be the top variable of {f, g, h};
+				let lvl = self.level(f).min(self.level(g)).min(self.level(h));
+				let (f_x, f_nx) = self.cofactors(f, lvl);
+				let (g_x, g_nx) = self.cofactors(g, lvl);
+				let (h_x, h_nx) = self.cofactors(h, lvl);
+				let t = self.ite_rec(f_x, g_x, h_x);
+				let e = self.ite_rec(f_nx, g_nx, h_nx);
+				let r = self.unique_insert(self.level_map.var(lvl), t, e);
+				self.computed_table.insert(entry_key, r);
+				r
+			}
+		};
+		if complement { self.not(r) } else { r }
+	}
+
+	fn quant_rec(&mut self, f: FunctionNode, cube: FunctionNode, op: Op) -> FunctionNode {
+		let one = func(term, false);
+		let zero = func(term, true);
+
+		// skip quantified vars above the top of f, f does not depend on them
+		let mut cube = cube;
+		while cube.head != term && self.level(cube) < self.level(f) {
+			if op == Op::Unique {
+				// f|x=1 ^ f|x=0 == f ^ f
+				return zero;
+			}
+			cube = func(self.nodes[cube.head].t_arc, false);
+		}
+
+		// if (terminal case) return result
+		if cube == one || f.head == term {
+			return f;
+		}
+
+		let entry_key = (op, f, cube, one);
+		match self.computed_table.get(&entry_key) {
+			Some(r) => r,
+			None => {
+				let lvl = self.level(f);
+				let (f_x, f_nx) = self.cofactors(f, lvl);
+				let r = if self.level(cube) == lvl {
+					// quantify out the top variable
+					let rem_cube = func(self.nodes[cube.head].t_arc, false);
+					let t = self.quant_rec(f_x, rem_cube, op);
+					if op == Op::Exists && t == one {
+						one
+					} else {
+						let e = self.quant_rec(f_nx, rem_cube, op);
+						if op == Op::Exists { self.or(t, e) } else { self.xor(t, e) }
+					}
+				} else {
+					let t = self.quant_rec(f_x, cube, op);
+					let e = self.quant_rec(f_nx, cube, op);
+					self.unique_insert(self.level_map.var(lvl), t, e)
+				};
+				self.computed_table.insert(entry_key, r);
+				r
+			}
+		}
+	}
+
+	fn and_exists_rec(&mut self, f: FunctionNode, g: FunctionNode, cube: FunctionNode) -> FunctionNode {
+		let one = func(term, false);
+		let zero = func(term, true);
+
+		// if (terminal case) return result
+		if f == zero || g == zero || (f.head == g.head && f.complement != g.complement) {
+			return zero;
+		} else if f == one || f == g {
+			return self.quant_rec(g, cube, Op::Exists);
+		} else if g == one {
+			return self.quant_rec(f, cube, Op::Exists);
+		}
+
+		// skip quantified vars above the top of {f, g}
+		let lvl = self.level(f).min(self.level(g));
+		let mut cube = cube;
+		while cube.head != term && self.level(cube) < lvl {
+			cube = func(self.nodes[cube.head].t_arc, false);
+		}
+		if cube == one {
+			return self.and(f, g);
+		}
+
+		let entry_key = if f < g { (Op::AndExists, f, g, cube) } else { (Op::AndExists, g, f, cube) };
+		match self.computed_table.get(&entry_key) {
+			Some(r) => r,
+			None => {
+				let (f_x, f_nx) = self.cofactors(f, lvl);
+				let (g_x, g_nx) = self.cofactors(g, lvl);
+				let r = if self.level(cube) == lvl {
+					// quantify out the top variable
+					let rem_cube = func(self.nodes[cube.head].t_arc, false);
+					let t = self.and_exists_rec(f_x, g_x, rem_cube);
+					if t == one {
+						one
+					} else {
+						let e = self.and_exists_rec(f_nx, g_nx, rem_cube);
+						self.or(t, e)
+					}
+				} else {
+					let t = self.and_exists_rec(f_x, g_x, cube);
+					let e = self.and_exists_rec(f_nx, g_nx, cube);
+					self.unique_insert(self.level_map.var(lvl), t, e)
+				};
+				self.computed_table.insert(entry_key, r);
+				r
+			}
+		}
+	}
+
+	// x_f is the single var function for the composed var
+	fn compose_rec(&mut self, f: FunctionNode, x_f: FunctionNode, g: FunctionNode) -> FunctionNode {
+		let lvl = self.level(f);
+		if lvl > self.level(x_f) {
+			// f does not depend on x
+			return f;
+		}
+
+		// compose(!f) == !compose(f)
+		let complement = f.complement;
+		let f = func(f.head, false);
+		let entry_key = (Op::Compose, f, g, x_f);
+		let r = match self.computed_table.get(&entry_key) {
+			Some(r) => r,
+			None => {
+				let (f_x, f_nx) = self.cofactors(f, lvl);
+				let r = if lvl == self.level(x_f) {
+					self.ite(g, f_x, f_nx)
+				} else {
+					let t = self.compose_rec(f_x, x_f, g);
+					let e = self.compose_rec(f_nx, x_f, g);
+					let top = self.var(self.level_map.var(lvl));
+					self.ite(top, t, e)
+				};
+				self.computed_table.insert(entry_key, r);
+				r
+			}
+		};
+		if complement { self.not(r) } else { r }
+	}
+
+	fn vector_compose_rec(&mut self, n: NodeIdx, subst: &HashMap<usize, FunctionNode>, max_lvl: usize, composed: &mut HashMap<NodeIdx, FunctionNode>) -> FunctionNode {
+		if n == term || self.level(func(n, false)) > max_lvl {
+			return func(n, false);
+		}
+		match composed.get(&n) {
+			Some(f) => *f,
+			None => {
+				let node = self.nodes[n].clone();
+				let t = self.vector_compose_rec(node.t_arc, subst, max_lvl, composed);
+				let mut e = self.vector_compose_rec(node.e_arc, subst, max_lvl, composed);
+				if node.e_complement {
+					e = self.not(e);
+				}
+				let x_f = match subst.get(&node.label) {
+					Some(g) => *g,
+					None => self.var(node.label),
+				};
+				let f = self.ite(x_f, t, e);
+				composed.insert(n, f);
+				f
+			}
+		}
+	}
+
+	fn relabel_rec<F>(&mut self, n: NodeIdx, rename: &F, relabeled: &mut HashMap<NodeIdx, FunctionNode>) -> FunctionNode
+		where F: Fn(usize) -> usize {
+		if n == term {
+			return func(term, false);
+		}
+		match relabeled.get(&n) {
+			Some(f) => *f,
+			None => {
+				let node = self.nodes[n].clone();
+				let t = self.relabel_rec(node.t_arc, rename, relabeled);
+				let e = self.relabel_rec(node.e_arc, rename, relabeled);
+				let f = self.unique_insert(rename(node.label), t, func(e.head, e.complement ^ node.e_complement));
+				relabeled.insert(n, f);
+				f
+			}
+		}
+	}
+
+	fn constrain_rec(&mut self, f: FunctionNode, c: FunctionNode) -> FunctionNode {
+		let one = func(term, false);
+		let zero = func(term, true);
+
+		// if (terminal case) return result
+		if c == zero {
+			// nothing to care about
+			return zero;
+		} else if c == one || f.head == term {
+			return f;
+		} else if f.head == c.head {
+			// f == c is 1 over c, f == !c is 0 over c
+			return func(term, f.complement != c.complement);
+		}
+
+		// constrain(!f, c) == !constrain(f, c)
+		let complement = f.complement;
+		let f = func(f.head, false);
+		let entry_key = (Op::Constrain, f, c, one);
+		let r = match self.computed_table.get(&entry_key) {
+			Some(r) => r,
+			None => {
+				let lvl = self.level(f).min(self.level(c));
+				let (f_x, f_nx) = self.cofactors(f, lvl);
+				let (c_x, c_nx) = self.cofactors(c, lvl);
+				let r = if c_x == zero {
+					self.constrain_rec(f_nx, c_nx)
+				} else if c_nx == zero {
+					self.constrain_rec(f_x, c_x)
+				} else {
+					let t = self.constrain_rec(f_x, c_x);
+					let e = self.constrain_rec(f_nx, c_nx);
+					self.unique_insert(self.level_map.var(lvl), t, e)
+				};
+				self.computed_table.insert(entry_key, r);
+				r
+			}
+		};
+		if complement { self.not(r) } else { r }
+	}
+
+	fn restrict_care_rec(&mut self, f: FunctionNode, c: FunctionNode) -> FunctionNode {
+		let one = func(term, false);
+		let zero = func(term, true);
+
+		// if (terminal case) return result
+		if c == zero {
+			return zero;
+		} else if c == one || f.head == term {
+			return f;
+		} else if f.head == c.head {
+			return func(term, f.complement != c.complement);
+		}
+
+		// restrict_care(!f, c) == !restrict_care(f, c)
+		let complement = f.complement;
+		let f = func(f.head, false);
+		let entry_key = (Op::RestrictCare, f, c, one);
+		let r = match self.computed_table.get(&entry_key) {
+			Some(r) => r,
+			None => {
+				let lvl = self.level(f);
+				let r = if self.level(c) < lvl {
+					// f does not depend on the top var of c, so abstract it
+					// away rather than letting it into the result
+					let c_lvl = self.level(c);
+					let (c_x, c_nx) = self.cofactors(c, c_lvl);
+					let c = self.or(c_x, c_nx);
+					self.restrict_care_rec(f, c)
+				} else {
+					let (f_x, f_nx) = self.cofactors(f, lvl);
+					let (c_x, c_nx) = self.cofactors(c, lvl);
+					if c_x == zero {
+						self.restrict_care_rec(f_nx, c_nx)
+					} else if c_nx == zero {
+						self.restrict_care_rec(f_x, c_x)
+					} else {
+						let t = self.restrict_care_rec(f_x, c_x);
+						let e = self.restrict_care_rec(f_nx, c_nx);
+						self.unique_insert(self.level_map.var(lvl), t, e)
+					}
+				};
+				self.computed_table.insert(entry_key, r);
+				r
+			}
+		};
+		if complement { self.not(r) } else { r }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rng::SplitMix64;
+	use crate::test_util::*;
+
+	#[test]
+	fn ite_matches_truth_tables() {
+		let mut rng = SplitMix64::new(3);
+		for i in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let ord = rand_ord(&mut rng, nv);
+			let es: Vec<Expr> = (0..3).map(|_| rand_expr(&mut rng, nv, 4)).collect();
+			let mut m = Manager::new(&ord);
+			let mut fs: Vec<FunctionNode> = es.iter().map(|e| m.from_expr(e)).collect();
+			// the terminal and complemented cases of the standard triples
+			match i % 4 {
+				1 => fs[1] = m.not(fs[0]),
+				2 => fs[2] = fs[0],
+				3 => fs[2] = m.constant(i % 8 == 3),
+				_ => {},
+			}
+			let r = m.ite(fs[0], fs[1], fs[2]);
+			for env in envs(nv) {
+				let (f, g, h) = (m.eval(fs[0], &env), m.eval(fs[1], &env), m.eval(fs[2], &env));
+				assert_eq!(m.eval(r, &env), if f { g } else { h });
+			}
+			// canonical, so the same function built another way is the same node
+			let fg = m.and(fs[0], fs[1]);
+			let nf = m.not(fs[0]);
+			let nfh = m.and(nf, fs[2]);
+			assert_eq!(m.or(fg, nfh), r);
+			let x = m.xor(fs[0], fs[1]);
+			let or = m.or(fs[0], fs[1]);
+			let nand = m.nand(fs[0], fs[1]);
+			assert_eq!(m.and(or, nand), x);
+		}
+	}
+
+	// e on every assignment to vars that agrees with env elsewhere
+	fn over_vars(e: &Expr, vars: &[usize], env: &Env) -> Vec<bool> {
+		(0..1usize << vars.len())
+			.map(|i| {
+				let mut env = env.clone();
+				for (j, &x) in vars.iter().enumerate() {
+					env[x] = (i >> j) & 1 == 1;
+				}
+				eval(e, &env)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn quantifiers_match_truth_tables() {
+		let mut rng = SplitMix64::new(4);
+		for _ in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let e = rand_expr(&mut rng, nv, 5);
+			let vars = rand_vars(&mut rng, nv);
+			let f = from(&e, &rand_ord(&mut rng, nv));
+			let (ex, fa, un) = (f.exists(&vars), f.forall(&vars), f.unique(&vars));
+			for env in envs(nv) {
+				let vals = over_vars(&e, &vars, &env);
+				assert_eq!(ex.eval(&env), vals.iter().any(|&b| b));
+				assert_eq!(fa.eval(&env), vals.iter().all(|&b| b));
+				assert_eq!(un.eval(&env), vals.iter().fold(false, |p, &b| p ^ b));
+			}
+		}
+	}
+
+	#[test]
+	fn and_exists_matches_and_then_exists() {
+		let mut rng = SplitMix64::new(5);
+		for i in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let e1 = rand_expr(&mut rng, nv, 5);
+			let e2 = rand_expr(&mut rng, nv, 5);
+			let vars = rand_vars(&mut rng, nv);
+			let mut m = Manager::new(&rand_ord(&mut rng, nv));
+			let f = m.from_expr(&e1);
+			let g = if i % 5 == 0 { f } else { m.from_expr(&e2) };
+			let cube = m.cube(&vars);
+			let r = m.and_exists(f, g, cube);
+			let fg = m.and(f, g);
+			assert_eq!(m.exists(fg, cube), r);
+			for env in envs(nv) {
+				let any = (0..1usize << vars.len()).any(|j| {
+					let mut env = env.clone();
+					for (k, &x) in vars.iter().enumerate() {
+						env[x] = (j >> k) & 1 == 1;
+					}
+					m.eval(f, &env) && m.eval(g, &env)
+				});
+				assert_eq!(m.eval(r, &env), any);
+			}
+			let b = m.export(f).and_exists(&m.export(g), &vars);
+			assert_eq!(m.import(&b), r);
+		}
+	}
+
+	#[test]
+	fn compose_matches_substitution() {
+		let mut rng = SplitMix64::new(7);
+		for _ in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let e = rand_expr(&mut rng, nv, 5);
+			let x = rand_below(&mut rng, nv);
+			let e_x = rand_expr(&mut rng, nv, 3);
+			let subst: Vec<(usize, Expr)> = rand_vars(&mut rng, nv).into_iter()
+				.map(|y| (y, rand_expr(&mut rng, nv, 3)))
+				.collect();
+
+			let mut m = Manager::new(&rand_ord(&mut rng, nv));
+			let f = m.from_expr(&e);
+			let g = m.from_expr(&e_x);
+			let r = m.compose(f, x, g);
+			let fs: HashMap<usize, FunctionNode> = subst.iter().map(|(y, e_y)| (*y, m.from_expr(e_y))).collect();
+			let r_vec = m.vector_compose(f, &fs);
+			for env in envs(nv) {
+				let mut composed = env.clone();
+				composed[x] = eval(&e_x, &env);
+				assert_eq!(m.eval(r, &env), eval(&e, &composed));
+				let mut composed = env.clone();
+				for (y, e_y) in subst.iter() {
+					composed[*y] = eval(e_y, &env);
+				}
+				assert_eq!(m.eval(r_vec, &env), eval(&e, &composed));
+			}
+			// substitution commutes with complement
+			let nf = m.not(f);
+			let nr = m.not(r);
+			assert_eq!(m.compose(nf, x, g), nr);
+		}
+	}
+
+	#[test]
+	fn permute_matches_relabeling() {
+		let mut rng = SplitMix64::new(8);
+		for i in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let ord = rand_ord(&mut rng, nv);
+			let e = rand_expr(&mut rng, nv, 5);
+			let perm = if i % 3 == 0 { (0..nv).collect() } else { rand_ord(&mut rng, nv) };
+			let mut m = Manager::new(&ord);
+			let f = m.from_expr(&e);
+			let r = m.permute(f, &perm);
+			// swapping the top two levels rarely keeps the order
+			let mut map = HashMap::new();
+			if nv >= 2 {
+				map.insert(ord[0], ord[1]);
+				map.insert(ord[1], ord[0]);
+			}
+			let r_map = m.rename(f, &map);
+			for env in envs(nv) {
+				let permuted: Env = (0..nv).map(|x| env[perm[x]]).collect();
+				assert_eq!(m.eval(r, &env), eval(&e, &permuted));
+				let renamed: Env = (0..nv).map(|x| env[*map.get(&x).unwrap_or(&x)]).collect();
+				assert_eq!(m.eval(r_map, &env), eval(&e, &renamed));
+			}
+		}
+	}
+
+	#[test]
+	fn order_keeping_rename_keeps_size() {
+		// even vars onto the odd var just below them
+		let mut m = Manager::new(&(0..6).collect::<Vec<usize>>());
+		let f = m.from_expr(&bin(Var(0), Or, bin(Var(2), And, not(Var(4)))));
+		let r = m.permute(f, &[1, 0, 3, 2, 5, 4]);
+		let g = m.from_expr(&bin(Var(1), Or, bin(Var(3), And, not(Var(5)))));
+		assert_eq!(r, g);
+		assert_eq!(m.size(r), m.size(f));
+	}
+
+	#[test]
+	fn care_set_ops_agree_on_care_set() {
+		let mut rng = SplitMix64::new(9);
+		for _ in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let e = rand_expr(&mut rng, nv, 5);
+			let e_care = rand_expr(&mut rng, nv, 4);
+			let mut m = Manager::new(&rand_ord(&mut rng, nv));
+			let f = m.from_expr(&e);
+			let c = m.from_expr(&e_care);
+			let r_con = m.constrain(f, c);
+			let r_care = m.restrict_care(f, c);
+			for env in envs(nv) {
+				if eval(&e_care, &env) {
+					assert_eq!(m.eval(r_con, &env), eval(&e, &env));
+					assert_eq!(m.eval(r_care, &env), eval(&e, &env));
+				}
+			}
+			// restrict_care never brings in vars f doesn't depend on
+			let support = m.support(f);
+			assert!(m.support(r_care).iter().all(|x| support.contains(x)));
+			// the Bdd versions are the same functions
+			let (b, b_care) = (m.export(f), m.export(c));
+			assert_eq!(m.import(&b.constrain(&b_care)), r_con);
+			assert_eq!(m.import(&b.restrict_care(&b_care)), r_care);
+		}
+	}
+}
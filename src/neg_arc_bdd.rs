@@ -8,6 +8,8 @@ use BOp::*;
 use crate::expr_rc::*;
 use ExprRc::*;
 
+use crate::manager::Manager;
+
 pub type NodeIdx = usize;
 
 #[derive(Debug)]
@@ -15,6 +17,8 @@ pub struct Bdd {
 	pub f: FunctionNode,
 	// list of nodes
 	pub nodes: Vec<InternalNode>,
+	// order the bdd was built with, first var is the root level
+	pub var_ord: Vec<usize>,
 	// indices: HashMap<InternalNode, NodeIdx>
 }
 
@@ -34,6 +38,53 @@ pub struct FunctionNode {
 
 pub const term: usize = std::usize::MAX;
 
+// Both directions of a variable order, so the level of a node is a lookup on
+// its label. The terminal sits at level num_vars, below every variable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LevelMap {
+	// var -> level
+	pub perm: Vec<usize>,
+	// level -> var, the order itself
+	pub invperm: Vec<usize>,
+}
+
+impl LevelMap {
+
+	pub fn new(var_ord: &[usize]) -> LevelMap {
+		let mut perm = vec![0; var_ord.len()];
+		for (lvl, &x) in var_ord.iter().enumerate() {
+			perm[x] = lvl;
+		}
+		LevelMap {
+			perm,
+			invperm: var_ord.to_vec(),
+		}
+	}
+
+	pub fn num_vars(&self) -> usize {
+		self.invperm.len()
+	}
+
+	pub fn level(&self, x: usize) -> usize {
+		self.perm[x]
+	}
+
+	pub fn var(&self, lvl: usize) -> usize {
+		self.invperm[lvl]
+	}
+
+	pub fn node_level(&self, n: NodeIdx, nodes: &[InternalNode]) -> usize {
+		if n == term { self.num_vars() } else { self.perm[nodes[n].label] }
+	}
+
+	// exchange the vars at lvl and lvl + 1
+	pub fn swap(&mut self, lvl: usize) {
+		self.invperm.swap(lvl, lvl + 1);
+		self.perm[self.invperm[lvl]] = lvl;
+		self.perm[self.invperm[lvl + 1]] = lvl + 1;
+	}
+}
+
 impl Bdd {
 
 	pub fn eval(&self, env: &Env) -> bool {
@@ -93,6 +144,203 @@ impl Bdd {
 	}
 }
 
+// Cofactoring a built bdd by a partial assignment
+impl Bdd {
+
+	// fix the vars in env, leaving a reduced bdd over the remaining vars
+	pub fn restrict(&self, env: &PartialEnv) -> Bdd {
+		self.restrict_with(|x| env.get(&x).cloned())
+	}
+
+	pub fn restrict_btree(&self, env: &PartialEnvBTree) -> Bdd {
+		self.restrict_with(|x| env.get(&x).cloned())
+	}
+
+	fn restrict_with<F>(&self, asgn: F) -> Bdd
+		where F: Fn(usize) -> Option<bool> {
+		fn rec<F>(bdd: &Bdd, n: NodeIdx, asgn: &F, restricted: &mut HashMap<NodeIdx, FunctionNode>, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode
+			where F: Fn(usize) -> Option<bool> {
+			if n == term {
+				return func(term, false);
+			}
+			match restricted.get(&n) {
+				Some(f) => *f,
+				None => {
+					let node = &bdd.nodes[n];
+					let f = match asgn(node.label) {
+						Some(true) => rec(bdd, node.t_arc, asgn, restricted, nodes, indices),
+						Some(false) => {
+							let e = rec(bdd, node.e_arc, asgn, restricted, nodes, indices);
+							func(e.head, e.complement ^ node.e_complement)
+						},
+						None => {
+							let pos_cof = rec(bdd, node.t_arc, asgn, restricted, nodes, indices);
+							let e = rec(bdd, node.e_arc, asgn, restricted, nodes, indices);
+							let neg_cof = func(e.head, e.complement ^ node.e_complement);
+							unique_insert_hash(node.label, pos_cof, neg_cof, nodes, indices)
+						},
+					};
+					restricted.insert(n, f);
+					f
+				}
+			}
+		}
+
+		let mut bdd = Bdd {
+			f: func(term, false),
+			nodes: Vec::new(),
+			var_ord: self.var_ord.clone(),
+		};
+		let f = rec(self, self.f.head, &asgn, &mut HashMap::new(), &mut bdd.nodes, &mut HashMap::new());
+		bdd.f = func(f.head, f.complement ^ self.f.complement);
+		bdd
+	}
+}
+
+// Operations on already built bdds.
+// Operands are pulled into a scratch manager in self's order, so the result
+// is reduced and canonical even if an operand was built with another order.
+impl Bdd {
+
+	pub fn not(&self) -> Bdd {
+		Bdd {
+			f: func(self.f.head, !self.f.complement),
+			nodes: self.nodes.clone(),
+			var_ord: self.var_ord.clone(),
+		}
+	}
+
+	pub fn and(&self, other: &Bdd) -> Bdd {
+		self.apply(other, Manager::and)
+	}
+
+	pub fn or(&self, other: &Bdd) -> Bdd {
+		self.apply(other, Manager::or)
+	}
+
+	pub fn xor(&self, other: &Bdd) -> Bdd {
+		self.apply(other, Manager::xor)
+	}
+
+	pub fn xnor(&self, other: &Bdd) -> Bdd {
+		self.apply(other, Manager::xnor)
+	}
+
+	pub fn nand(&self, other: &Bdd) -> Bdd {
+		self.apply(other, Manager::nand)
+	}
+
+	pub fn nor(&self, other: &Bdd) -> Bdd {
+		self.apply(other, Manager::nor)
+	}
+
+	pub fn implies(&self, other: &Bdd) -> Bdd {
+		self.apply(other, Manager::implies)
+	}
+
+	// if self then g else h
+	pub fn ite(&self, g: &Bdd, h: &Bdd) -> Bdd {
+		self.in_manager(|manager, f| {
+			let g = manager.import(g);
+			let h = manager.import(h);
+			manager.ite(f, g, h)
+		})
+	}
+
+	// existential quantification of vars, f|x=1 || f|x=0 for each x
+	pub fn exists(&self, vars: &[usize]) -> Bdd {
+		self.in_manager(|manager, f| {
+			let cube = manager.cube(vars);
+			manager.exists(f, cube)
+		})
+	}
+
+	// universal quantification of vars, f|x=1 && f|x=0 for each x
+	pub fn forall(&self, vars: &[usize]) -> Bdd {
+		self.in_manager(|manager, f| {
+			let cube = manager.cube(vars);
+			manager.forall(f, cube)
+		})
+	}
+
+	// unique quantification of vars, f|x=1 ^ f|x=0 for each x
+	pub fn unique(&self, vars: &[usize]) -> Bdd {
+		self.in_manager(|manager, f| {
+			let cube = manager.cube(vars);
+			manager.unique(f, cube)
+		})
+	}
+
+	// relational product, exists vars. self && other in a single pass
+	pub fn and_exists(&self, other: &Bdd, vars: &[usize]) -> Bdd {
+		self.in_manager(|manager, f| {
+			let g = manager.import(other);
+			let cube = manager.cube(vars);
+			manager.and_exists(f, g, cube)
+		})
+	}
+
+	// self with var x replaced by g
+	pub fn compose(&self, x: usize, g: &Bdd) -> Bdd {
+		self.in_manager(|manager, f| {
+			let g = manager.import(g);
+			manager.compose(f, x, g)
+		})
+	}
+
+	// self with every var in subst simultaneously replaced by its bdd
+	pub fn vector_compose(&self, subst: &HashMap<usize, Bdd>) -> Bdd {
+		self.in_manager(|manager, f| {
+			let subst = subst.iter()
+				.map(|(&x, g)| (x, manager.import(g)))
+				.collect();
+			manager.vector_compose(f, &subst)
+		})
+	}
+
+	// relabel every var x as perm[x]
+	pub fn permute(&self, perm: &[usize]) -> Bdd {
+		self.in_manager(|manager, f| manager.permute(f, perm))
+	}
+
+	// relabel the vars found in map, others keep their label
+	pub fn rename(&self, map: &HashMap<usize, usize>) -> Bdd {
+		self.in_manager(|manager, f| manager.rename(f, map))
+	}
+
+	// generalized cofactor by care set, agrees with self wherever care holds
+	pub fn constrain(&self, care: &Bdd) -> Bdd {
+		self.in_manager(|manager, f| {
+			let c = manager.import(care);
+			manager.constrain(f, c)
+		})
+	}
+
+	// Coudert-Madre restrict by care set, agrees with self wherever care
+	// holds without picking up vars self does not depend on
+	pub fn restrict_care(&self, care: &Bdd) -> Bdd {
+		self.in_manager(|manager, f| {
+			let c = manager.import(care);
+			manager.restrict_care(f, c)
+		})
+	}
+
+	fn apply(&self, other: &Bdd, op: fn(&mut Manager, FunctionNode, FunctionNode) -> FunctionNode) -> Bdd {
+		self.in_manager(|manager, f| {
+			let g = manager.import(other);
+			op(manager, f, g)
+		})
+	}
+
+	fn in_manager<F>(&self, op: F) -> Bdd
+		where F: FnOnce(&mut Manager, FunctionNode) -> FunctionNode {
+		let mut manager = Manager::new(&self.var_ord);
+		let f = manager.import(self);
+		let r = op(&mut manager, f);
+		manager.export(r)
+	}
+}
+
 pub fn func(head: NodeIdx, complement: bool) -> FunctionNode {
 	FunctionNode {
 		head: head,
@@ -166,32 +414,33 @@ pub fn unique_insert_btree(x: usize, pos_cof: FunctionNode, neg_cof: FunctionNod
 
 /////////////////////////
 pub fn from(e: &Expr, var_ord: &[usize]) -> Bdd {
-	fn rec(e: &Expr, rem_support: &[usize], cof_asgn: &mut Env, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
-		if rem_support.is_empty() {
-			// No more cofactors to check, eval and create node.
-			// unit terminal is true, so false representation requires complementation
-			func(term, !eval(e, cof_asgn))
-		} else {
-			let x = rem_support[0];
-			// Calculate positive and negative cofactors for current var and recurse
-			cof_asgn[x] = true;
-			let pos_cof = rec(e, &rem_support[1..], cof_asgn, nodes, indices);
-			cof_asgn[x] = false;
-			let neg_cof = rec(e, &rem_support[1..], cof_asgn, nodes, indices);
-			unique_insert_hash(x, pos_cof, neg_cof, nodes, indices)
-		}
-	}
-
 	let mut bdd = Bdd {
 		f: func(0, false),
 		nodes: Vec::new(),
+		var_ord: var_ord.to_vec(),
 		// indices: HashMap::new(),
 	};
 	let mut cof_asgn = vec![false; var_ord.len()];
-	bdd.f = rec(e, var_ord, &mut cof_asgn, &mut bdd.nodes, &mut HashMap::new());
+	bdd.f = from_rec(e, 0, &LevelMap::new(var_ord), &mut cof_asgn, &mut bdd.nodes, &mut HashMap::new());
 	bdd
 }
 
+pub(crate) fn from_rec(e: &Expr, lvl: usize, levels: &LevelMap, cof_asgn: &mut Env, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
+	if lvl == levels.num_vars() {
+		// No more cofactors to check, eval and create node.
+		// unit terminal is true, so false representation requires complementation
+		func(term, !eval(e, cof_asgn))
+	} else {
+		let x = levels.var(lvl);
+		// Calculate positive and negative cofactors for current var and recurse
+		cof_asgn[x] = true;
+		let pos_cof = from_rec(e, lvl + 1, levels, cof_asgn, nodes, indices);
+		cof_asgn[x] = false;
+		let neg_cof = from_rec(e, lvl + 1, levels, cof_asgn, nodes, indices);
+		unique_insert_hash(x, pos_cof, neg_cof, nodes, indices)
+	}
+}
+
 ////////////////////////
 
 #[derive(PartialEq)]
@@ -246,24 +495,24 @@ fn in_support(x: usize, e: &Expr, cof_asgn: &mut PartialEnv) -> bool {
 }
 
 pub fn from_support(e: &Expr, var_ord: &[usize]) -> Bdd {
-	fn rec(e: &Expr, rem_support: &[usize], cof_asgn: &mut PartialEnv, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
-		if rem_support.is_empty() {
+	fn rec(e: &Expr, lvl: usize, levels: &LevelMap, cof_asgn: &mut PartialEnv, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
+		if lvl == levels.num_vars() {
 			// No more cofactors to check, eval and create node.
 			// unit terminal is true, so false representation requires complementation
 			func(term, !eval_partial(e, cof_asgn))
 		} else {
-			let x = rem_support[0];
+			let x = levels.var(lvl);
 			if in_support(x, e, cof_asgn) {
 				// Calculate positive and negative cofactors for current var and recurse
 				cof_asgn.insert(x, true);
-				let pos_cof = rec(e, &rem_support[1..], cof_asgn, nodes, indices);
+				let pos_cof = rec(e, lvl + 1, levels, cof_asgn, nodes, indices);
 				cof_asgn.insert(x, false);
-				let neg_cof = rec(e, &rem_support[1..], cof_asgn, nodes, indices);
+				let neg_cof = rec(e, lvl + 1, levels, cof_asgn, nodes, indices);
 				cof_asgn.remove(&x);
 				unique_insert_hash(x, pos_cof, neg_cof, nodes, indices)
 			} else {
 				cof_asgn.insert(x, false); // doesn't matter, needed for eval
-				let rec = rec(e, &rem_support[1..], cof_asgn, nodes, indices);
+				let rec = rec(e, lvl + 1, levels, cof_asgn, nodes, indices);
 				cof_asgn.remove(&x);
 				rec
 			}
@@ -273,10 +522,11 @@ pub fn from_support(e: &Expr, var_ord: &[usize]) -> Bdd {
 	let mut bdd = Bdd {
 		f: func(0, false),
 		nodes: Vec::new(),
+		var_ord: var_ord.to_vec(),
 		// indices: HashMap::new(),
 	};
 	let mut cof_asgn: PartialEnv = HashMap::new();
-	bdd.f = rec(e, var_ord, &mut cof_asgn, &mut bdd.nodes, &mut HashMap::new());
+	bdd.f = rec(e, 0, &LevelMap::new(var_ord), &mut cof_asgn, &mut bdd.nodes, &mut HashMap::new());
 	bdd
 }
 
@@ -316,7 +566,7 @@ pub fn subst_and_simplify(e: &Expr, x: usize, b: bool) -> Expr {
 }
 
 // Only used after simplification, so if the variable is here it is in the support of the func
-fn in_support_simplified(x: usize, e: &Expr) -> bool {
+pub(crate) fn in_support_simplified(x: usize, e: &Expr) -> bool {
 	match e {
 		Lit(_) => false,
 		Var(x2) => *x2 == x,
@@ -326,43 +576,44 @@ fn in_support_simplified(x: usize, e: &Expr) -> bool {
 }
 
 pub fn from_support_simplified(e: &Expr, var_ord: &[usize]) -> Bdd {
-	fn rec(e: &Expr, rem_support: &[usize], cof_asgn: &mut Env, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
-		if rem_support.is_empty() {
-			// No more cofactors to check, eval and create node.
-			// unit terminal is true, so false representation requires complementation
-			func(term, !eval(e, cof_asgn))
-		} else {
-			let x = rem_support[0];
-			if in_support_simplified(x, e) {
-				// Calculate positive and negative cofactors for current var and recurse
-				cof_asgn[x] = true;
-				let e_pos = subst_and_simplify(e, x, true);
-				let pos_cof = rec(&e_pos, &rem_support[1..], cof_asgn, nodes, indices);
-				
-				cof_asgn[x] = false;
-				let e_neg = subst_and_simplify(e, x, false);
-				let neg_cof = rec(&e_neg, &rem_support[1..], cof_asgn, nodes, indices);
-
-				unique_insert_hash(x, pos_cof, neg_cof, nodes, indices)
-			} else {
-				// cof_asgn[x] = false; // doesn't matter, needed for eval and set by default
-				// no need to simplify again, already factored out
-				let rec = rec(e, &rem_support[1..], cof_asgn, nodes, indices);
-				rec
-			}
-		}
-	}
-
 	let mut bdd = Bdd {
 		f: func(0, false),
 		nodes: vec![],
+		var_ord: var_ord.to_vec(),
 		// indices: HashMap::new(),
 	};
 	let mut cof_asgn: Env = vec![false; var_ord.len()];
-	bdd.f = rec(e, var_ord, &mut cof_asgn, &mut bdd.nodes, &mut HashMap::new());
+	bdd.f = from_support_simplified_rec(e, 0, &LevelMap::new(var_ord), &mut cof_asgn, &mut bdd.nodes, &mut HashMap::new());
 	bdd
 }
 
+pub(crate) fn from_support_simplified_rec(e: &Expr, lvl: usize, levels: &LevelMap, cof_asgn: &mut Env, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
+	if lvl == levels.num_vars() {
+		// No more cofactors to check, eval and create node.
+		// unit terminal is true, so false representation requires complementation
+		func(term, !eval(e, cof_asgn))
+	} else {
+		let x = levels.var(lvl);
+		if in_support_simplified(x, e) {
+			// Calculate positive and negative cofactors for current var and recurse
+			cof_asgn[x] = true;
+			let e_pos = subst_and_simplify(e, x, true);
+			let pos_cof = from_support_simplified_rec(&e_pos, lvl + 1, levels, cof_asgn, nodes, indices);
+			
+			cof_asgn[x] = false;
+			let e_neg = subst_and_simplify(e, x, false);
+			let neg_cof = from_support_simplified_rec(&e_neg, lvl + 1, levels, cof_asgn, nodes, indices);
+
+			unique_insert_hash(x, pos_cof, neg_cof, nodes, indices)
+		} else {
+			// cof_asgn[x] = false; // doesn't matter, needed for eval and set by default
+			// no need to simplify again, already factored out
+			let rec = from_support_simplified_rec(e, lvl + 1, levels, cof_asgn, nodes, indices);
+			rec
+		}
+	}
+}
+
 /////////////////////////
 
 enum SimplifyResult {
@@ -439,28 +690,28 @@ fn in_support_simplified_rc(x: usize, e: &ExprRc) -> bool {
 }
 
 pub fn from_support_simplified_rc(e: &ExprRc, var_ord: &[usize]) -> Bdd {
-	fn rec(e: &ExprRc, rem_support: &[usize], cof_asgn: &mut Env, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
-		if rem_support.is_empty() {
+	fn rec(e: &ExprRc, lvl: usize, levels: &LevelMap, cof_asgn: &mut Env, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
+		if lvl == levels.num_vars() {
 			// No more cofactors to check, eval and create node.
 			// unit terminal is true, so false representation requires complementation
 			func(term, !eval_rc(e, cof_asgn))
 		} else {
-			let x = rem_support[0];
+			let x = levels.var(lvl);
 			if in_support_simplified_rc(x, e) {
 				// Calculate positive and negative cofactors for current var and recurse
 				cof_asgn[x] = true;
 				let e_pos = subst_and_simplify_rc(e, x, true);
-				let pos_cof = rec(&e_pos, &rem_support[1..], cof_asgn, nodes, indices);
+				let pos_cof = rec(&e_pos, lvl + 1, levels, cof_asgn, nodes, indices);
 				
 				cof_asgn[x] = false;
 				let e_neg = subst_and_simplify_rc(e, x, false);
-				let neg_cof = rec(&e_neg, &rem_support[1..], cof_asgn, nodes, indices);
+				let neg_cof = rec(&e_neg, lvl + 1, levels, cof_asgn, nodes, indices);
 
 				unique_insert_hash(x, pos_cof, neg_cof, nodes, indices)
 			} else {
 				// cof_asgn[x] = false; // doesn't matter, needed for eval and set by default
 				// no need to simplify again, already factored out
-				let rec = rec(e, &rem_support[1..], cof_asgn, nodes, indices);
+				let rec = rec(e, lvl + 1, levels, cof_asgn, nodes, indices);
 				rec
 			}
 		}
@@ -469,34 +720,35 @@ pub fn from_support_simplified_rc(e: &ExprRc, var_ord: &[usize]) -> Bdd {
 	let mut bdd = Bdd {
 		f: func(0, false),
 		nodes: vec![],
+		var_ord: var_ord.to_vec(),
 		// indices: HashMap::new(),
 	};
 	let mut cof_asgn: Env = vec![false; var_ord.len()];
-	bdd.f = rec(e, var_ord, &mut cof_asgn, &mut bdd.nodes, &mut HashMap::new());
+	bdd.f = rec(e, 0, &LevelMap::new(var_ord), &mut cof_asgn, &mut bdd.nodes, &mut HashMap::new());
 	bdd
 }
 
 ////////////////////////
 pub fn from_support_btree(e: &Expr, var_ord: &[usize]) -> Bdd {
-	fn rec(e: &Expr, rem_support: &[usize], cof_asgn: &mut PartialEnvBTree, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
-		if rem_support.is_empty() {
+	fn rec(e: &Expr, lvl: usize, levels: &LevelMap, cof_asgn: &mut PartialEnvBTree, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
+		if lvl == levels.num_vars() {
 			// No more cofactors to check, eval and create node.
 			// unit terminal is true, so false representation requires complementation
 			func(term, !eval_partialbtree(e, cof_asgn))
 		} else {
-			let x = rem_support[0];
+			let x = levels.var(lvl);
 			if in_support_btree(x, e, cof_asgn) {
 				// Calculate positive and negative cofactors for current var and recurse
 				cof_asgn.insert(x, true);
-				let pos_cof = rec(e, &rem_support[1..], cof_asgn, nodes, indices);
+				let pos_cof = rec(e, lvl + 1, levels, cof_asgn, nodes, indices);
 				cof_asgn.insert(x, false);
-				let neg_cof = rec(e, &rem_support[1..], cof_asgn, nodes, indices);
+				let neg_cof = rec(e, lvl + 1, levels, cof_asgn, nodes, indices);
 				cof_asgn.remove(&x);
 
 				unique_insert_hash(x, pos_cof, neg_cof, nodes, indices)
 			} else {
 				cof_asgn.insert(x, false); // doesn't matter, needed for eval
-				let rec = rec(e, &rem_support[1..], cof_asgn, nodes, indices);
+				let rec = rec(e, lvl + 1, levels, cof_asgn, nodes, indices);
 				cof_asgn.remove(&x);
 				rec
 			}
@@ -506,10 +758,11 @@ pub fn from_support_btree(e: &Expr, var_ord: &[usize]) -> Bdd {
 	let mut bdd = Bdd {
 		f: func(0, false),
 		nodes: Vec::new(),
+		var_ord: var_ord.to_vec(),
 		// indices: HashMap::new(),
 	};
 	let mut cof_asgn: PartialEnvBTree = BTreeMap::new();
-	bdd.f = rec(e, var_ord, &mut cof_asgn, &mut bdd.nodes, &mut HashMap::new());
+	bdd.f = rec(e, 0, &LevelMap::new(var_ord), &mut cof_asgn, &mut bdd.nodes, &mut HashMap::new());
 	bdd
 }
 
@@ -559,44 +812,45 @@ fn in_support_btree(x: usize, e: &Expr, cof_asgn: &mut PartialEnvBTree) -> bool
 //////////////////////////////////////////////////////////
 
 pub fn from_support_vec(e: &Expr, var_ord: &[usize]) -> Bdd {
-	fn rec(e: &Expr, rem_support: &[usize], cof_asgn: &mut Env, cof_valid: &mut Env, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
-		if rem_support.is_empty() {
-			// No more cofactors to check, eval and create node.
-			// unit terminal is true, so false representation requires complementation
-			func(term, !eval(e, cof_asgn))
-		} else {
-			let x = rem_support[0];
-			if in_support_vec(x, e, cof_asgn, cof_valid) {
-				// Calculate positive and negative cofactors for current var and recurse
-				cof_asgn[x] = true;
-				cof_valid[x] = true;
-				let pos_cof = rec(e, &rem_support[1..], cof_asgn, cof_valid, nodes, indices);
-				cof_asgn[x] = false;
-				let neg_cof = rec(e, &rem_support[1..], cof_asgn, cof_valid, nodes, indices);
-				cof_valid[x] = false;
-				unique_insert_hash(x, pos_cof, neg_cof, nodes, indices)
-			} else {
-				cof_asgn[x] = false; // doesn't matter, needed for eval
-				cof_valid[x] = true;
-				let rec = rec(e, &rem_support[1..], cof_asgn, cof_valid, nodes, indices);
-				cof_valid[x] = false;
-				rec
-			}
-		}
-	}
-
 	let mut bdd = Bdd {
 		f: func(0, false),
 		nodes: vec![],
+		var_ord: var_ord.to_vec(),
 		// indices: HashMap::new(),
 	};
 	let mut cof_asgn: Env = vec![false; var_ord.len()];
 	let mut cof_valid: Vec<bool> = vec![false; var_ord.len()];
-	bdd.f = rec(e, var_ord, &mut cof_asgn, &mut cof_valid, &mut bdd.nodes, &mut HashMap::new());
+	bdd.f = from_support_vec_rec(e, 0, &LevelMap::new(var_ord), &mut cof_asgn, &mut cof_valid, &mut bdd.nodes, &mut HashMap::new());
 	bdd
 }
 
-fn in_support_vec(x: usize, e: &Expr, cof_asgn: &mut Env, cof_valid: &mut Env) -> bool {
+pub(crate) fn from_support_vec_rec(e: &Expr, lvl: usize, levels: &LevelMap, cof_asgn: &mut Env, cof_valid: &mut Env, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
+	if lvl == levels.num_vars() {
+		// No more cofactors to check, eval and create node.
+		// unit terminal is true, so false representation requires complementation
+		func(term, !eval(e, cof_asgn))
+	} else {
+		let x = levels.var(lvl);
+		if in_support_vec(x, e, cof_asgn, cof_valid) {
+			// Calculate positive and negative cofactors for current var and recurse
+			cof_asgn[x] = true;
+			cof_valid[x] = true;
+			let pos_cof = from_support_vec_rec(e, lvl + 1, levels, cof_asgn, cof_valid, nodes, indices);
+			cof_asgn[x] = false;
+			let neg_cof = from_support_vec_rec(e, lvl + 1, levels, cof_asgn, cof_valid, nodes, indices);
+			cof_valid[x] = false;
+			unique_insert_hash(x, pos_cof, neg_cof, nodes, indices)
+		} else {
+			cof_asgn[x] = false; // doesn't matter, needed for eval
+			cof_valid[x] = true;
+			let rec = from_support_vec_rec(e, lvl + 1, levels, cof_asgn, cof_valid, nodes, indices);
+			cof_valid[x] = false;
+			rec
+		}
+	}
+}
+
+pub(crate) fn in_support_vec(x: usize, e: &Expr, cof_asgn: &mut Env, cof_valid: &mut Env) -> bool {
 	fn rec(x: usize, e: &Expr, cof_asgn: &mut Env, cof_valid: &mut Env) -> SupportResult {
 		match e {
 			Lit(b) => IndependantConst(*b),
@@ -637,3 +891,87 @@ fn in_support_vec(x: usize, e: &Expr, cof_asgn: &mut Env, cof_valid: &mut Env) -
 	rec(x, e, cof_asgn, cof_valid) == Dependant
 }
 
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rng::{Rng, SplitMix64};
+	use crate::test_util::*;
+
+	// apply op and what it computes on one assignment
+	type BinOp = (fn(&Bdd, &Bdd) -> Bdd, fn(bool, bool) -> bool);
+
+	#[test]
+	fn apply_matches_truth_tables() {
+		let ops: [BinOp; 7] = [
+			(Bdd::and, |a, b| a && b),
+			(Bdd::or, |a, b| a || b),
+			(Bdd::xor, |a, b| a ^ b),
+			(Bdd::xnor, |a, b| a == b),
+			(Bdd::nand, |a, b| !(a && b)),
+			(Bdd::nor, |a, b| !(a || b)),
+			(Bdd::implies, |a, b| !a || b),
+		];
+		let mut rng = SplitMix64::new(2);
+		for _ in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let e1 = rand_expr(&mut rng, nv, 5);
+			let e2 = rand_expr(&mut rng, nv, 5);
+			// operands built in different orders and by different constructors
+			let f = from_support_vec(&e1, &rand_ord(&mut rng, nv));
+			let g = crate::combin_bdd::from_combinatorial_hash(&e2, &rand_ord(&mut rng, nv));
+			for (op, sem) in ops.iter() {
+				let r = op(&f, &g);
+				for env in envs(nv) {
+					assert_eq!(r.eval(&env), sem(eval(&e1, &env), eval(&e2, &env)));
+				}
+				// no dead nodes left in the result, and it is reduced
+				assert_eq!(r.nodes.len(), r.size());
+				let mut m = Manager::new(&r.var_ord);
+				let h = m.import(&r);
+				assert_eq!(m.size(h), r.size());
+			}
+			let nf = f.not();
+			for env in envs(nv) {
+				assert_eq!(nf.eval(&env), !eval(&e1, &env));
+			}
+		}
+	}
+
+	#[test]
+	fn restrict_matches_substitution() {
+		let mut rng = SplitMix64::new(6);
+		for _ in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let ord = rand_ord(&mut rng, nv);
+			let e = rand_expr(&mut rng, nv, 5);
+			let mut env_map = PartialEnv::new();
+			let mut env_btree = PartialEnvBTree::new();
+			for x in rand_vars(&mut rng, nv) {
+				let b = rng.next_bool();
+				env_map.insert(x, b);
+				env_btree.insert(x, b);
+			}
+			let f = from(&e, &ord);
+			let r = f.restrict(&env_map);
+			let r_btree = f.restrict_btree(&env_btree);
+
+			// reduced, the same size as building the substituted expression
+			let mut e_sub = e.clone();
+			for (&x, &b) in env_map.iter() {
+				e_sub = subst_and_simplify(&e_sub, x, b);
+			}
+			let size = from(&e_sub, &ord).size();
+			assert_eq!(r.size(), size);
+			assert_eq!(r_btree.size(), size);
+			for env in envs(nv) {
+				let mut fixed = env.clone();
+				for (&x, &b) in env_map.iter() {
+					fixed[x] = b;
+				}
+				assert_eq!(r.eval(&env), eval(&e, &fixed));
+				assert_eq!(r_btree.eval(&env), eval(&e, &fixed));
+			}
+		}
+	}
+}
@@ -0,0 +1,193 @@
+use crate::expr::*;
+use Expr::*;
+
+// Static variable order heuristics.
+// Each looks only at the shape of an expression and returns a var_ord for
+// num_vars variables, vars the expression never mentions go last.
+
+// FORCE stops after this many passes even if the span keeps shrinking
+const FORCE_MAX_PASSES: usize = 100;
+
+// Depth first, visiting the deeper operand of each Binary first (fan-in), so
+// vars feeding long chains of logic end up near the root
+pub fn dfs(e: &Expr, num_vars: usize) -> Vec<usize> {
+	let (_, ord) = dfs_rec(e, num_vars);
+	complete(ord, num_vars)
+}
+
+// depth of e and the order of its vars
+fn dfs_rec(e: &Expr, num_vars: usize) -> (usize, Vec<usize>) {
+	match e {
+		Lit(_) => (0, Vec::new()),
+		Var(x) => (0, vec![*x]),
+		Not(e1) => dfs_rec(e1, num_vars),
+		Binary(e1, _, e2) => {
+			let (d1, ord1) = dfs_rec(e1, num_vars);
+			let (d2, ord2) = dfs_rec(e2, num_vars);
+			let (first, second) = if d2 > d1 { (ord2, ord1) } else { (ord1, ord2) };
+			let mut seen = vec![false; num_vars];
+			let ord = first.into_iter().chain(second)
+				.filter(|&x| !std::mem::replace(&mut seen[x], true))
+				.collect();
+			(d1.max(d2) + 1, ord)
+		},
+	}
+}
+
+// Order of the left operand, with the new vars of the right operand slotted
+// in right after the shared var they follow, so vars used together in the
+// same Binary subtree stay close. Operands with no vars in common are just
+// placed one after the other.
+pub fn interleave(e: &Expr, num_vars: usize) -> Vec<usize> {
+	complete(interleave_rec(e), num_vars)
+}
+
+fn interleave_rec(e: &Expr) -> Vec<usize> {
+	match e {
+		Lit(_) => Vec::new(),
+		Var(x) => vec![*x],
+		Not(e1) => interleave_rec(e1),
+		Binary(e1, _, e2) => {
+			let mut ord = interleave_rec(e1);
+			// position of the last var of e2 placed so far
+			let mut anchor: Option<usize> = None;
+			// new vars seen before any shared one, they go in front of it
+			let mut pending = Vec::new();
+			for x in interleave_rec(e2) {
+				match ord.iter().position(|&y| y == x) {
+					Some(p) => {
+						let n_pending = pending.len();
+						ord.splice(p..p, pending.drain(..));
+						anchor = Some(p + n_pending);
+					},
+					None => match anchor {
+						Some(a) => {
+							ord.insert(a + 1, x);
+							anchor = Some(a + 1);
+						},
+						None => pending.push(x),
+					},
+				}
+			}
+			ord.extend(pending);
+			ord
+		},
+	}
+}
+
+// FORCE hypergraph placement starting from init. Every Binary subtree is a
+// hyperedge over its support, each pass moves vars to the average center of
+// gravity of their edges, until the total edge span stops shrinking.
+pub fn force(e: &Expr, init: &[usize]) -> Vec<usize> {
+	let num_vars = init.len();
+	let mut edges = Vec::new();
+	support_edges(e, num_vars, &mut edges);
+
+	let mut ord = init.to_vec();
+	let mut span = total_span(&edges, &ord);
+	for _ in 0..FORCE_MAX_PASSES {
+		let pos = positions(&ord);
+		let mut gravity = vec![0.0; num_vars];
+		let mut degree = vec![0; num_vars];
+		for edge in &edges {
+			let cog = edge.iter().map(|&x| pos[x] as f64).sum::<f64>() / edge.len() as f64;
+			for &x in edge {
+				gravity[x] += cog;
+				degree[x] += 1;
+			}
+		}
+		let target: Vec<f64> = (0..num_vars)
+			.map(|x| if degree[x] == 0 { pos[x] as f64 } else { gravity[x] / degree[x] as f64 })
+			.collect();
+		let mut next = ord.clone();
+		// ties keep their current relative order
+		next.sort_by(|&x, &y| target[x].partial_cmp(&target[y]).unwrap().then(pos[x].cmp(&pos[y])));
+		let next_span = total_span(&edges, &next);
+		if next_span >= span {
+			break;
+		}
+		ord = next;
+		span = next_span;
+	}
+	ord
+}
+
+// support of e, pushing the support of every Binary subtree onto edges
+fn support_edges(e: &Expr, num_vars: usize, edges: &mut Vec<Vec<usize>>) -> Vec<bool> {
+	match e {
+		Lit(_) => vec![false; num_vars],
+		Var(x) => {
+			let mut support = vec![false; num_vars];
+			support[*x] = true;
+			support
+		},
+		Not(e1) => support_edges(e1, num_vars, edges),
+		Binary(e1, _, e2) => {
+			let mut support = support_edges(e1, num_vars, edges);
+			for (x, b) in support_edges(e2, num_vars, edges).into_iter().enumerate() {
+				support[x] |= b;
+			}
+			let edge: Vec<usize> = (0..num_vars).filter(|&x| support[x]).collect();
+			// single var edges can't pull anything together
+			if edge.len() > 1 {
+				edges.push(edge);
+			}
+			support
+		},
+	}
+}
+
+// sum over edges of the distance between their outermost vars
+fn total_span(edges: &[Vec<usize>], ord: &[usize]) -> usize {
+	let pos = positions(ord);
+	edges.iter()
+		.map(|edge| {
+			let lvls = edge.iter().map(|&x| pos[x]);
+			lvls.clone().max().unwrap() - lvls.min().unwrap()
+		})
+		.sum()
+}
+
+// var -> level
+fn positions(ord: &[usize]) -> Vec<usize> {
+	let mut pos = vec![0; ord.len()];
+	for (lvl, &x) in ord.iter().enumerate() {
+		pos[x] = lvl;
+	}
+	pos
+}
+
+// append the vars ord doesn't mention
+fn complete(mut ord: Vec<usize>, num_vars: usize) -> Vec<usize> {
+	let mut seen = vec![false; num_vars];
+	for &x in &ord {
+		seen[x] = true;
+	}
+	ord.extend((0..num_vars).filter(|&x| !seen[x]));
+	ord
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::neg_arc_bdd::from;
+
+	#[test]
+	fn heuristics_interleave_the_comparator() {
+		for n_bits in [3, 4, 6] {
+			let (e, bad, good) = gen::comparator(n_bits);
+			let n = 2 * n_bits;
+			let good_size = from(&e, &good).size();
+			assert!(good_size < from(&e, &bad).size());
+
+			assert_eq!(dfs(&e, n), good);
+			assert_eq!(force(&e, &bad), good);
+			// the pairs from the last bit up, each x above its y
+			let pairs: Vec<usize> = (0..n_bits).rev().flat_map(|i| [2 * i, 2 * i + 1]).collect();
+			assert_eq!(interleave(&e, n), pairs);
+			for ord in [dfs(&e, n), interleave(&e, n), force(&e, &bad)] {
+				assert_eq!(from(&e, &ord).size(), good_size);
+			}
+		}
+	}
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::ops::Not;
+
+use crate::cache::*;
+use crate::expr::*;
+use crate::neg_arc_bdd;
+use crate::neg_arc_bdd::LevelMap;
+use Expr::*;
+use BOp::*;
+
+// Same complement arc bdds as neg_arc_bdd, in a compact layout: indices are
+// u32 and an edge keeps its complement bit in the low bit of the index, so a
+// node takes 12 bytes instead of 32 and an edge 4 instead of 16. Only the
+// standalone construction below uses it, Manager keeps its usize nodes.
+
+// index of the terminal, the largest that still fits once shifted
+pub const TERM: u32 = u32::MAX >> 1;
+
+// node index shifted left once, low bit set when complemented
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Edge(u32);
+
+// unit terminal is true, so false representation requires complementation
+pub const ONE: Edge = Edge(TERM << 1);
+pub const ZERO: Edge = Edge((TERM << 1) | 1);
+
+impl Edge {
+
+	pub fn new(head: u32, complement: bool) -> Edge {
+		Edge((head << 1) | complement as u32)
+	}
+
+	pub fn head(self) -> u32 {
+		self.0 >> 1
+	}
+
+	pub fn complement(self) -> bool {
+		self.0 & 1 == 1
+	}
+}
+
+impl Not for Edge {
+	type Output = Edge;
+
+	fn not(self) -> Edge {
+		Edge(self.0 ^ 1)
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PackedNode {
+	pub label: u32,
+	// never complemented
+	pub t_arc: Edge,
+	pub e_arc: Edge,
+}
+
+#[derive(Debug)]
+pub struct PackedBdd {
+	pub f: Edge,
+	pub nodes: Vec<PackedNode>,
+	// order the bdd was built with, first var is the root level
+	pub var_ord: Vec<usize>,
+}
+
+impl PackedBdd {
+
+	pub fn eval(&self, env: &Env) -> bool {
+		let mut f = self.f;
+		while f.head() != TERM {
+			let node = &self.nodes[f.head() as usize];
+			let next = if env[node.label as usize] { node.t_arc } else { node.e_arc };
+			f = if f.complement() { next.not() } else { next };
+		}
+		!f.complement()
+	}
+
+	// size of reachable bdd, not including terminal
+	pub fn size(&self) -> usize {
+		let mut visited = vec![false; self.nodes.len()];
+		let mut stack = vec![self.f.head()];
+		let mut count = 0;
+		while let Some(n) = stack.pop() {
+			if n != TERM && !visited[n as usize] {
+				visited[n as usize] = true;
+				count += 1;
+				stack.push(self.nodes[n as usize].t_arc.head());
+				stack.push(self.nodes[n as usize].e_arc.head());
+			}
+		}
+		count
+	}
+
+	// repack a bdd node for node, indices stay the same
+	pub fn from_bdd(bdd: &neg_arc_bdd::Bdd) -> PackedBdd {
+		assert!(bdd.nodes.len() < TERM as usize, "too many nodes to pack in 32 bits");
+		let pack = |n: usize, complement: bool| {
+			Edge::new(if n == neg_arc_bdd::term { TERM } else { n as u32 }, complement)
+		};
+		PackedBdd {
+			f: pack(bdd.f.head, bdd.f.complement),
+			nodes: bdd.nodes.iter()
+				.map(|node| PackedNode {
+					label: node.label as u32,
+					t_arc: pack(node.t_arc, false),
+					e_arc: pack(node.e_arc, node.e_complement),
+				})
+				.collect(),
+			var_ord: bdd.var_ord.clone(),
+		}
+	}
+
+	pub fn to_bdd(&self) -> neg_arc_bdd::Bdd {
+		let unpack = |e: Edge| {
+			let head = if e.head() == TERM { neg_arc_bdd::term } else { e.head() as usize };
+			neg_arc_bdd::func(head, e.complement())
+		};
+		neg_arc_bdd::Bdd {
+			f: unpack(self.f),
+			nodes: self.nodes.iter()
+				.map(|node| neg_arc_bdd::InternalNode {
+					label: node.label as usize,
+					t_arc: unpack(node.t_arc).head,
+					e_arc: unpack(node.e_arc).head,
+					e_complement: node.e_arc.complement(),
+				})
+				.collect(),
+			var_ord: self.var_ord.clone(),
+		}
+	}
+}
+
+// Computed table of the packed and construction
+pub type PackedAndCache = ComputedCache<(Edge, Edge), Edge>;
+
+pub fn unique_insert(x: u32, pos_cof: Edge, neg_cof: Edge, nodes: &mut Vec<PackedNode>, table: &mut HashMap<PackedNode, u32>) -> Edge {
+	if pos_cof == neg_cof {
+		// Both arcs point to same thing, no need for a node
+		return pos_cof;
+	}
+	// (move pos_cof negation to this func, flip since whole func is negated)
+	let complement = pos_cof.complement();
+	let node = PackedNode {
+		label: x,
+		t_arc: if complement { pos_cof.not() } else { pos_cof },
+		e_arc: if complement { neg_cof.not() } else { neg_cof },
+	};
+	let i = match table.get(&node) {
+		Some(&i) => i,
+		None => {
+			let i = nodes.len() as u32;
+			assert!(i < TERM, "too many nodes to pack in 32 bits");
+			table.insert(node, i);
+			nodes.push(node);
+			i
+		},
+	};
+	Edge::new(i, complement)
+}
+
+// combin_bdd::from_combinatorial_hash in the packed layout
+pub fn from_combinatorial_hash(e: &Expr, var_ord: &[usize]) -> PackedBdd {
+	fn rec(e: &Expr,
+		   levels: &LevelMap,
+		   nodes: &mut Vec<PackedNode>,
+		   unique_table: &mut HashMap<PackedNode, u32>,
+		   computed_table: &mut PackedAndCache) -> Edge {
+		match e {
+			Lit(b) => if *b { ONE } else { ZERO },
+			Var(x) => unique_insert(*x as u32, ONE, ZERO, nodes, unique_table),
+			Not(e1) => rec(e1, levels, nodes, unique_table, computed_table).not(),
+			Binary(e1, And, e2) => {
+				let f1 = rec(e1, levels, nodes, unique_table, computed_table);
+				let f2 = rec(e2, levels, nodes, unique_table, computed_table);
+				and(f1, f2, levels, nodes, unique_table, computed_table)
+			},
+			Binary(e1, Or , e2) => {
+				// apply and using demorgan
+				let f1 = rec(e1, levels, nodes, unique_table, computed_table).not();
+				let f2 = rec(e2, levels, nodes, unique_table, computed_table).not();
+				and(f1, f2, levels, nodes, unique_table, computed_table).not()
+			},
+		}
+	}
+
+	let mut bdd = PackedBdd {
+		f: ONE,
+		nodes: Vec::new(),
+		var_ord: var_ord.to_vec(),
+	};
+	let mut cache = PackedAndCache::new(DEFAULT_CACHE_SLOTS);
+	bdd.f = rec(e, &LevelMap::new(var_ord), &mut bdd.nodes, &mut HashMap::new(), &mut cache);
+	bdd
+}
+
+fn and(f: Edge,
+       g: Edge,
+       levels: &LevelMap,
+       nodes: &mut Vec<PackedNode>,
+       unique_table: &mut HashMap<PackedNode, u32>,
+       computed_table: &mut PackedAndCache) -> Edge {
+	// if (terminal case) return result
+	if f.head() == g.head() {
+		// f == g || f == !g
+		if f == g { f } else { ZERO }
+	} else if f.head() == TERM {
+		// f == 0 || f == 1
+		if f == ZERO { f } else { g }
+	} else if g.head() == TERM {
+		// g == 0 || g == 1
+		if g == ZERO { g } else { f }
+	} else {
+		// if (computed table has entry ({f, g}, r)) return r;
+		let entry_key = if f < g { (f, g) } else { (g, f) };
+		match computed_table.get(&entry_key) {
+			Some(r) => r,
+			None => {
+				let coff = |f: Edge, x: u32| -> (Edge, Edge) {
+					let f_node = &nodes[f.head() as usize];
+					if f_node.label == x {
+						if f.complement() {
+							(f_node.t_arc.not(), f_node.e_arc.not())
+						} else {
+							(f_node.t_arc, f_node.e_arc)
+						}
+					} else {
+						(f, f)
+					}
+				};
+
+				// let x be the top variable of {f,g};
+				let level = |f: Edge| levels.level(nodes[f.head() as usize].label as usize);
+				let x = levels.var(level(f).min(level(g))) as u32;
+				let (f_x, f_nx) = coff(f, x);
+				let (g_x, g_nx) = coff(g, x);
+
+				let t = and(f_x, g_x, levels, nodes, unique_table, computed_table);
+				let e = and(f_nx, g_nx, levels, nodes, unique_table, computed_table);
+
+				// r = findOrAddUniqueTable(x, t, e);
+				let r = unique_insert(x, t, e, nodes, unique_table);
+				computed_table.insert(entry_key, r);
+				r
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::combin_bdd;
+	use crate::rng::SplitMix64;
+	use crate::test_util::*;
+
+	#[test]
+	fn packed_build_matches_usize_build() {
+		let mut rng = SplitMix64::new(19);
+		let (comp, bad, _) = gen::comparator(4);
+		let mut cases = vec![(comp, bad)];
+		for _ in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 7);
+			cases.push((rand_expr(&mut rng, nv, 6), rand_ord(&mut rng, nv)));
+		}
+		for (e, var_ord) in cases {
+			let packed = from_combinatorial_hash(&e, &var_ord);
+			let plain = combin_bdd::from_combinatorial_hash(&e, &var_ord);
+			// same nodes in the same slots
+			let unpacked = packed.to_bdd();
+			assert_eq!(unpacked.f, plain.f);
+			assert_eq!(unpacked.nodes, plain.nodes);
+			assert_eq!(packed.size(), plain.size());
+			assert_eq!(PackedBdd::from_bdd(&plain).nodes, packed.nodes);
+			for env in envs(var_ord.len()) {
+				assert_eq!(packed.eval(&env), eval(&e, &env));
+			}
+		}
+	}
+}
@@ -0,0 +1,620 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use crate::cache::*;
+use crate::combin_bdd::AndCache;
+use crate::expr::*;
+use crate::neg_arc_bdd::*;
+use Expr::*;
+use BOp::*;
+
+// Multi-threaded combin_bdd::from_combinatorial_hash.
+// A fixed pool of workers, each with a deque of jobs. Where the sequential
+// construction runs two recursions one after the other (the operands of a
+// Binary, the cofactors of an and) the first is pushed on the back of the
+// worker's deque and the second is run right away. Idle workers steal from
+// the front of the other deques, backing off to short sleeps while there is
+// nothing to take, and a job nobody stole is popped back and run by its
+// owner. Workers share a unique table and a computed table, each
+// split into shards. Nodes never move or change once inserted, so reading
+// one takes no lock, only inserting does. The unique table still holds every
+// node once, so the result is the same bdd as the sequential one, only
+// numbered differently until it is renumbered children first on the way out.
+
+// shards the unique and computed tables are split into, a power of 2
+const SHARDS: usize = 64;
+
+// recursions deeper than this never fork, their subproblems are too small
+const FORK_DEPTH: usize = 12;
+
+// the first chunk of a node store holds 1 << FIRST_CHUNK_BITS nodes, each
+// next one twice as many
+const FIRST_CHUNK_BITS: u32 = 8;
+const CHUNKS: usize = 40;
+
+// an idle worker yields this many times before it starts sleeping, and
+// never sleeps longer than MAX_SLEEP_MICROS at once
+const YIELD_ROUNDS: u32 = 16;
+const MAX_SLEEP_MICROS: u64 = 1000;
+
+// Append-only node storage. Chunks are allocated as they are needed and
+// never move, so nodes can be read while others are being added.
+struct Store {
+	chunks: [OnceLock<Box<[OnceLock<InternalNode>]>>; CHUNKS],
+}
+
+impl Store {
+
+	fn new() -> Store {
+		Store {
+			chunks: std::array::from_fn(|_| OnceLock::new()),
+		}
+	}
+
+	// chunk and offset of slot i
+	fn locate(i: usize) -> (usize, usize) {
+		let p = i + (1 << FIRST_CHUNK_BITS);
+		let bits = usize::BITS - 1 - p.leading_zeros();
+		((bits - FIRST_CHUNK_BITS) as usize, p - (1 << bits))
+	}
+
+	fn get(&self, i: usize) -> &InternalNode {
+		let (c, off) = Store::locate(i);
+		self.chunks[c].get()
+			.and_then(|chunk| chunk[off].get())
+			.expect("node read before it was inserted")
+	}
+
+	// slots are only set once, by whoever holds the shard's table
+	fn set(&self, i: usize, node: InternalNode) {
+		let (c, off) = Store::locate(i);
+		let chunk = self.chunks[c].get_or_init(|| {
+			(0..1usize << (c as u32 + FIRST_CHUNK_BITS)).map(|_| OnceLock::new()).collect()
+		});
+		chunk[off].set(node).expect("node slot set twice");
+	}
+}
+
+// Waiting between looks for a job when there was none: a worker yields for
+// the first few rounds, then sleeps twice as long each round up to a cap, so
+// a long stretch with nothing to steal doesn't keep every core busy
+struct Backoff {
+	rounds: u32,
+}
+
+impl Backoff {
+
+	fn new() -> Backoff {
+		Backoff { rounds: 0 }
+	}
+
+	fn reset(&mut self) {
+		self.rounds = 0;
+	}
+
+	fn idle(&mut self) {
+		if self.rounds < YIELD_ROUNDS {
+			thread::yield_now();
+		} else {
+			let exp = (self.rounds - YIELD_ROUNDS).min(MAX_SLEEP_MICROS.ilog2());
+			thread::sleep(Duration::from_micros((1 << exp).min(MAX_SLEEP_MICROS)));
+		}
+		self.rounds = self.rounds.saturating_add(1);
+	}
+}
+
+// Node index n lives in shard n % SHARDS, at n / SHARDS in its store
+struct Shard {
+	table: Mutex<HashMap<InternalNode, usize>>,
+	store: Store,
+}
+
+// a recursion that can be handed to another worker
+#[derive(Clone, Copy)]
+enum Task<'a> {
+	Rec(&'a Expr, usize),
+	And(FunctionNode, FunctionNode, usize),
+}
+
+struct Job<'a> {
+	task: Task<'a>,
+	// set once the task has run, wherever it ran
+	result: Arc<OnceLock<FunctionNode>>,
+}
+
+struct Shared<'a> {
+	levels: &'a LevelMap,
+	unique_table: Vec<Shard>,
+	computed_table: Vec<Mutex<AndCache>>,
+	// one per worker, the owner works at the back and thieves at the front
+	deques: Vec<Mutex<VecDeque<Job<'a>>>>,
+	// set once the root is built, the pool stops looking for jobs
+	done: AtomicBool,
+}
+
+// from_combinatorial_hash on a pool of threads workers, the caller included.
+// The result is renumbered children first, like after Bdd::compact.
+pub fn from_combinatorial_par(e: &Expr, var_ord: &[usize], threads: usize) -> Bdd {
+	let levels = LevelMap::new(var_ord);
+	let threads = threads.max(1);
+	let shared = Shared {
+		levels: &levels,
+		unique_table: (0..SHARDS)
+			.map(|_| Shard { table: Mutex::new(HashMap::new()), store: Store::new() })
+			.collect(),
+		computed_table: (0..SHARDS).map(|_| Mutex::new(AndCache::new(DEFAULT_CACHE_SLOTS / SHARDS))).collect(),
+		deques: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+		done: AtomicBool::new(false),
+	};
+	// the calling thread is worker 0
+	let f = thread::scope(|s| {
+		for w in 1..threads {
+			let shared = &shared;
+			s.spawn(move || shared.work(w));
+		}
+		let f = shared.rec(0, e, 0);
+		shared.done.store(true, Ordering::Release);
+		f
+	});
+
+	let mut bdd = Bdd {
+		f,
+		nodes: Vec::new(),
+		var_ord: var_ord.to_vec(),
+	};
+	let mut moved: Vec<Vec<NodeIdx>> = shared.unique_table.iter()
+		.map(|shard| vec![term; shard.table.lock().unwrap().len()])
+		.collect();
+	bdd.f.head = export(&shared.unique_table, f.head, &mut bdd.nodes, &mut moved);
+	bdd
+}
+
+impl<'a> Shared<'a> {
+
+	fn rec(&self, w: usize, e: &'a Expr, depth: usize) -> FunctionNode {
+		match e {
+			Lit(b) => func(term, !b),
+			Var(x) => self.unique_insert(*x, func(term, false), func(term, true)),
+			Not(e1) => {
+				let mut f = self.rec(w, e1, depth);
+				f.complement = !f.complement;
+				f
+			},
+			Binary(e1, And, e2) => {
+				let (f1, f2) = self.join(w, depth, Task::Rec(e1, depth + 1), Task::Rec(e2, depth + 1));
+				self.and(w, f1, f2, depth)
+			},
+			Binary(e1, Or , e2) => {
+				// apply and using demorgan
+				let (mut f1, mut f2) = self.join(w, depth, Task::Rec(e1, depth + 1), Task::Rec(e2, depth + 1));
+				f1.complement = !f1.complement;
+				f2.complement = !f2.complement;
+				let mut f = self.and(w, f1, f2, depth);
+				f.complement = !f.complement;
+				f
+			},
+		}
+	}
+
+	fn and(&self, w: usize, f: FunctionNode, g: FunctionNode, depth: usize) -> FunctionNode {
+		// if (terminal case) return result
+		if f.head == g.head {
+			// f == g || f == !g
+			if f.complement == g.complement { f } else { func(term, true) }
+		} else if f.head == term {
+			// f == 0 || f == 1
+			if f.complement { f } else { g }
+		} else if g.head == term {
+			// g == 0 || g == 1
+			if g.complement { g } else { f }
+		} else {
+			// if (computed table has entry ({f, g}, r)) return r;
+			let entry_key = if f < g { (f, g) } else { (g, f) };
+			let (a, b) = entry_key;
+			let cache = &self.computed_table[shard_of(&[a.head, a.complement as usize, b.head, b.complement as usize])];
+			if let Some(r) = cache.lock().unwrap().get(&entry_key) {
+				return r;
+			}
+
+			let coff = |f: FunctionNode, x: usize| -> (FunctionNode, FunctionNode) {
+				let f_node = self.node(f.head);
+				if f_node.label == x {
+					(func(f_node.t_arc, f.complement), func(f_node.e_arc, f_node.e_complement ^ f.complement))
+				} else {
+					(f, f)
+				}
+			};
+
+			// let x be the top variable of {f,g};
+			let lvl = self.levels.level(self.node(f.head).label).min(self.levels.level(self.node(g.head).label));
+			let x = self.levels.var(lvl);
+			let (f_x, f_nx) = coff(f, x);
+			let (g_x, g_nx) = coff(g, x);
+
+			let (t, e) = self.join(w, depth, Task::And(f_x, g_x, depth + 1), Task::And(f_nx, g_nx, depth + 1));
+
+			// two workers may get here with the same node, the unique table
+			// keeps whichever inserts first
+			let r = self.unique_insert(x, t, e);
+			cache.lock().unwrap().insert(entry_key, r);
+			r
+		}
+	}
+
+	// Run a and b on worker w, offering a to the rest of the pool first. If
+	// no one has taken it by the time b is done it runs here after all.
+	fn join(&self, w: usize, depth: usize, a: Task<'a>, b: Task<'a>) -> (FunctionNode, FunctionNode) {
+		if self.deques.len() == 1 || depth >= FORK_DEPTH {
+			return (self.run(w, a), self.run(w, b));
+		}
+		let result = Arc::new(OnceLock::new());
+		self.deques[w].lock().unwrap().push_back(Job { task: a, result: result.clone() });
+		let rb = self.run(w, b);
+
+		// whatever b pushed has been joined by now, so a is at the back
+		// unless it was stolen
+		let own = {
+			let mut deque = self.deques[w].lock().unwrap();
+			let on_top = deque.back().is_some_and(|job| Arc::ptr_eq(&job.result, &result));
+			if on_top { deque.pop_back() } else { None }
+		};
+		let ra = match own {
+			Some(job) => self.run(w, job.task),
+			None => {
+				let mut backoff = Backoff::new();
+				loop {
+					if let Some(&r) = result.get() {
+						break r;
+					}
+					// help out while the thief finishes it
+					match self.steal(w) {
+						Some(job) => {
+							self.execute(w, job);
+							backoff.reset();
+						},
+						None => backoff.idle(),
+					}
+				}
+			},
+		};
+		(ra, rb)
+	}
+
+	fn run(&self, w: usize, task: Task<'a>) -> FunctionNode {
+		match task {
+			Task::Rec(e, depth) => self.rec(w, e, depth),
+			Task::And(f, g, depth) => self.and(w, f, g, depth),
+		}
+	}
+
+	fn execute(&self, w: usize, job: Job<'a>) {
+		let r = self.run(w, job.task);
+		job.result.set(r).expect("job ran twice");
+	}
+
+	// oldest job of the first other worker that has one
+	fn steal(&self, w: usize) -> Option<Job<'a>> {
+		let n = self.deques.len();
+		(1..n).find_map(|i| self.deques[(w + i) % n].lock().unwrap().pop_front())
+	}
+
+	// loop of the pool workers, stealing until the root is built
+	fn work(&self, w: usize) {
+		let mut backoff = Backoff::new();
+		while !self.done.load(Ordering::Acquire) {
+			match self.steal(w) {
+				Some(job) => {
+					self.execute(w, job);
+					backoff.reset();
+				},
+				None => backoff.idle(),
+			}
+		}
+	}
+
+	fn node(&self, n: NodeIdx) -> &InternalNode {
+		self.unique_table[n % SHARDS].store.get(n / SHARDS)
+	}
+
+	// unique_insert_hash over the shards
+	fn unique_insert(&self, x: usize, pos_cof: FunctionNode, neg_cof: FunctionNode) -> FunctionNode {
+		if pos_cof == neg_cof {
+			// Both arcs point to same thing, no need for a node
+			return pos_cof;
+		}
+		// (move pos_cof negation to this func, flip since whole func is negated)
+		let node = InternalNode {
+			label: x,
+			t_arc: pos_cof.head,
+			e_arc: neg_cof.head,
+			e_complement: neg_cof.complement ^ pos_cof.complement,
+		};
+		let s = shard_of(&[x, node.t_arc, node.e_arc, node.e_complement as usize]);
+		let shard = &self.unique_table[s];
+		let mut table = shard.table.lock().unwrap();
+		let i = match table.get(&node) {
+			Some(&i) => i,
+			None => {
+				// stored before it is listed, so no one can find it unset
+				let i = table.len();
+				shard.store.set(i, node.clone());
+				table.insert(node, i);
+				i
+			},
+		};
+		func(i * SHARDS + s, pos_cof.complement)
+	}
+}
+
+// shard of a node or computed table key from its words, a multiplicative
+// mix is enough to spread them and much cheaper than the tables' own hash
+fn shard_of(words: &[usize]) -> usize {
+	let h = words.iter().fold(0u64, |h, &w| (h ^ w as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15).rotate_left(29));
+	(h >> 40) as usize & (SHARDS - 1)
+}
+
+// copy the nodes n reaches out of the shards, children first. moved[s][i]
+// is where slot i of shard s went, term until it is copied.
+fn export(shards: &[Shard], n: NodeIdx, nodes: &mut Vec<InternalNode>, moved: &mut [Vec<NodeIdx>]) -> NodeIdx {
+	if n == term {
+		return term;
+	}
+	let (s, i) = (n % SHARDS, n / SHARDS);
+	if moved[s][i] != term {
+		return moved[s][i];
+	}
+	let node = shards[s].store.get(i);
+	let t_arc = export(shards, node.t_arc, nodes, moved);
+	let e_arc = export(shards, node.e_arc, nodes, moved);
+	nodes.push(InternalNode {
+		label: node.label,
+		t_arc,
+		e_arc,
+		e_complement: node.e_complement,
+	});
+	moved[s][i] = nodes.len() - 1;
+	moved[s][i]
+}
+
+/////////////////////////
+
+// Parallel Shannon expansion.
+// The top k levels are expanded here, leaving one task per cofactor below
+// them. Worker threads take tasks off a shared counter, each building its
+// cofactor with the sequential recursion into a node table of its own. The
+// tables are then merged through a single unique table, which also joins
+// cofactors that turned out equal, and the top k levels are rebuilt over them.
+
+// parallel neg_arc_bdd::from, splitting the top k levels across threads
+pub fn from_par(e: &Expr, var_ord: &[usize], k: usize, threads: usize) -> Bdd {
+	let root = vec![false; var_ord.len()];
+	from_split(var_ord, k, threads, root,
+		|cof_asgn, x| {
+			let mut pos = cof_asgn.clone();
+			pos[x] = true;
+			let mut neg = cof_asgn.clone();
+			neg[x] = false;
+			Split(pos, neg)
+		},
+		|cof_asgn, lvl, levels, nodes, indices| {
+			from_rec(e, lvl, levels, &mut cof_asgn.clone(), nodes, indices)
+		})
+}
+
+// parallel neg_arc_bdd::from_support_vec, vars e doesn't depend on under the
+// current cofactor are skipped without splitting, as in the sequential one
+pub fn from_support_vec_par(e: &Expr, var_ord: &[usize], k: usize, threads: usize) -> Bdd {
+	let root = (vec![false; var_ord.len()], vec![false; var_ord.len()]);
+	from_split(var_ord, k, threads, root,
+		|(cof_asgn, cof_valid), x| {
+			let mut cof_asgn = cof_asgn.clone();
+			let mut cof_valid = cof_valid.clone();
+			let dependant = in_support_vec(x, e, &mut cof_asgn, &mut cof_valid);
+			cof_valid[x] = true;
+			if dependant {
+				let mut pos = cof_asgn.clone();
+				pos[x] = true;
+				cof_asgn[x] = false;
+				Split((pos, cof_valid.clone()), (cof_asgn, cof_valid))
+			} else {
+				// doesn't matter, needed for eval
+				cof_asgn[x] = false;
+				Skip((cof_asgn, cof_valid))
+			}
+		},
+		|(cof_asgn, cof_valid), lvl, levels, nodes, indices| {
+			from_support_vec_rec(e, lvl, levels, &mut cof_asgn.clone(), &mut cof_valid.clone(), nodes, indices)
+		})
+}
+
+// parallel neg_arc_bdd::from_support_simplified, each task gets its own
+// cofactor of e already simplified
+pub fn from_support_simplified_par(e: &Expr, var_ord: &[usize], k: usize, threads: usize) -> Bdd {
+	let root = (e.clone(), vec![false; var_ord.len()]);
+	from_split(var_ord, k, threads, root,
+		|(e, cof_asgn), x| {
+			if in_support_simplified(x, e) {
+				let mut pos = cof_asgn.clone();
+				pos[x] = true;
+				let mut neg = cof_asgn.clone();
+				neg[x] = false;
+				Split((subst_and_simplify(e, x, true), pos), (subst_and_simplify(e, x, false), neg))
+			} else {
+				// no need to simplify again, already factored out
+				Skip((e.clone(), cof_asgn.clone()))
+			}
+		},
+		|(e, cof_asgn), lvl, levels, nodes, indices| {
+			from_support_simplified_rec(e, lvl, levels, &mut cof_asgn.clone(), nodes, indices)
+		})
+}
+
+// what expanding a cofactor state on a var gives
+enum Expand<T> {
+	Split(T, T),
+	// the var doesn't matter here, no node for it
+	Skip(T),
+}
+use Expand::*;
+
+// the top k levels, leaves index the tasks
+enum Top {
+	Leaf(usize),
+	Node(usize, Box<Top>, Box<Top>),
+}
+
+fn from_split<T, X, B>(var_ord: &[usize], k: usize, threads: usize, root: T, expand: X, build: B) -> Bdd
+	where T: Sync,
+	      X: Fn(&T, usize) -> Expand<T>,
+	      B: Fn(&T, usize, &LevelMap, &mut Vec<InternalNode>, &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode + Sync {
+	let levels = LevelMap::new(var_ord);
+	let k = k.min(var_ord.len());
+	let mut tasks = Vec::new();
+	let top = split(root, 0, k, &levels, &expand, &mut tasks);
+
+	// each task's root and node table, in task order
+	let next = AtomicUsize::new(0);
+	let mut built: Vec<(usize, FunctionNode, Vec<InternalNode>)> = thread::scope(|s| {
+		let workers: Vec<_> = (0..threads.max(1).min(tasks.len()))
+			.map(|_| s.spawn(|| {
+				let mut done = Vec::new();
+				loop {
+					let i = next.fetch_add(1, Ordering::Relaxed);
+					if i >= tasks.len() {
+						return done;
+					}
+					let mut nodes = Vec::new();
+					let f = build(&tasks[i], k, &levels, &mut nodes, &mut HashMap::new());
+					done.push((i, f, nodes));
+				}
+			}))
+			.collect();
+		workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+	});
+	built.sort_by_key(|&(i, _, _)| i);
+
+	let mut bdd = Bdd {
+		f: func(term, false),
+		nodes: Vec::new(),
+		var_ord: var_ord.to_vec(),
+	};
+	let mut indices = HashMap::new();
+	let roots: Vec<FunctionNode> = built.into_iter()
+		.map(|(_, f, nodes)| merge(f, nodes, &mut bdd.nodes, &mut indices))
+		.collect();
+	bdd.f = join(&top, &roots, &mut bdd.nodes, &mut indices);
+	// same numbering as the sequential constructors
+	bdd.compact();
+	bdd
+}
+
+fn split<T, X>(state: T, lvl: usize, k: usize, levels: &LevelMap, expand: &X, tasks: &mut Vec<T>) -> Top
+	where X: Fn(&T, usize) -> Expand<T> {
+	if lvl == k {
+		tasks.push(state);
+		Top::Leaf(tasks.len() - 1)
+	} else {
+		let x = levels.var(lvl);
+		match expand(&state, x) {
+			Split(pos, neg) => Top::Node(x,
+				Box::new(split(pos, lvl + 1, k, levels, expand, tasks)),
+				Box::new(split(neg, lvl + 1, k, levels, expand, tasks))),
+			Skip(state) => split(state, lvl + 1, k, levels, expand, tasks),
+		}
+	}
+}
+
+// Insert a task's nodes into the shared table, returning where f ended up.
+// Children always come before their parents in a task's table.
+fn merge(f: FunctionNode, sub: Vec<InternalNode>, nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
+	let mut moved: Vec<NodeIdx> = Vec::with_capacity(sub.len());
+	let relocate = |n: NodeIdx, moved: &[NodeIdx]| if n == term { term } else { moved[n] };
+	for node in sub {
+		let t_arc = relocate(node.t_arc, &moved);
+		let e_arc = relocate(node.e_arc, &moved);
+		// then arc is regular, so the node comes back as is
+		let r = unique_insert_hash(node.label, func(t_arc, false), func(e_arc, node.e_complement), nodes, indices);
+		moved.push(r.head);
+	}
+	func(relocate(f.head, &moved), f.complement)
+}
+
+fn join(top: &Top, roots: &[FunctionNode], nodes: &mut Vec<InternalNode>, indices: &mut HashMap<InternalNode, NodeIdx>) -> FunctionNode {
+	match top {
+		Top::Leaf(i) => roots[*i],
+		Top::Node(x, pos, neg) => {
+			let pos_cof = join(pos, roots, nodes, indices);
+			let neg_cof = join(neg, roots, nodes, indices);
+			unique_insert_hash(*x, pos_cof, neg_cof, nodes, indices)
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::combin_bdd;
+	use crate::rng::SplitMix64;
+	use crate::test_util::*;
+
+	// expressions and orders to build, from tiny to too big for one shard
+	fn cases() -> Vec<(Expr, Vec<usize>)> {
+		let mut rng = SplitMix64::new(20);
+		let (comp, bad, good) = gen::comparator(8);
+		let mut cases = vec![(comp.clone(), bad), (comp, good), (Lit(true), vec![0]), (Var(0), vec![0])];
+		for _ in 0..40 {
+			let nv = 1 + rand_below(&mut rng, 8);
+			cases.push((rand_expr(&mut rng, nv, 8), rand_ord(&mut rng, nv)));
+		}
+		cases
+	}
+
+	#[test]
+	fn par_and_matches_sequential() {
+		for (e, var_ord) in cases() {
+			let mut seq = combin_bdd::from_combinatorial_hash(&e, &var_ord);
+			seq.compact();
+			for threads in [1, 2, 3, 4, 8] {
+				let par = from_combinatorial_par(&e, &var_ord, threads);
+				assert_eq!(par.f, seq.f);
+				assert_eq!(par.nodes, seq.nodes);
+			}
+		}
+	}
+
+	type Build = fn(&Expr, &[usize]) -> Bdd;
+	type ParBuild = fn(&Expr, &[usize], usize, usize) -> Bdd;
+
+	#[test]
+	fn par_shannon_matches_sequential() {
+		let builds: [(Build, ParBuild); 3] = [
+			(from, from_par),
+			(from_support_vec, from_support_vec_par),
+			(from_support_simplified, from_support_simplified_par),
+		];
+		let mut rng = SplitMix64::new(21);
+		let (comp, bad, _) = gen::comparator(3);
+		let mut cases = vec![(comp, bad), (Lit(false), vec![0, 1])];
+		for _ in 0..20 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			cases.push((rand_expr(&mut rng, nv, 6), rand_ord(&mut rng, nv)));
+		}
+		for (e, var_ord) in cases {
+			for (build, par_build) in builds {
+				let mut seq = build(&e, &var_ord);
+				seq.compact();
+				// from no split at all to every level split
+				for k in 0..=var_ord.len() {
+					for threads in [1, 3] {
+						let par = par_build(&e, &var_ord, k, threads);
+						assert_eq!(par.f, seq.f);
+						assert_eq!(par.nodes, seq.nodes);
+					}
+				}
+			}
+		}
+	}
+}
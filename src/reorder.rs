@@ -0,0 +1,731 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::expr::Env;
+use crate::manager::Manager;
+use crate::neg_arc_bdd::*;
+
+// Sifting gives up on a direction once the table grows past this factor of
+// the best size seen so far for the var being moved
+const MAX_GROWTH: f64 = 1.2;
+
+// Largest support optimal_order takes on. Two layers of its search are
+// live at once, about 70MB of tables here and some 5GB at 20 vars.
+const MAX_EXACT_VARS: usize = 16;
+
+// Bookkeeping that only lives for one reordering session
+struct ReorderState {
+	// parent arcs plus protections pointing at each node, 0 means dead
+	refs: Vec<usize>,
+}
+
+// Levels moved as one unit while reordering
+struct Blocks {
+	// vars of each block from its top level down, empty once merged away
+	members: Vec<Vec<usize>>,
+	// var -> block
+	block_of: Vec<usize>,
+}
+
+// Dynamic variable reordering.
+// Levels are swapped in place in the shared node table: a node keeps its
+// index and the function it stands for, only its label and arcs change.
+// Protected functions therefore stay valid across a reordering, while any
+// other handle may point at a node that was freed along the way and whose
+// slot was reused. This holds for automatic reordering too, so with it on
+// every handle kept from one operation to the next must be protected.
+impl Manager {
+
+	// keep f alive across reordering, calls nest
+	pub fn protect(&mut self, f: FunctionNode) {
+		if f.head != term {
+			*self.protected.entry(f.head).or_insert(0) += 1;
+		}
+	}
+
+	pub fn unprotect(&mut self, f: FunctionNode) {
+		if let Some(count) = self.protected.get_mut(&f.head) {
+			*count -= 1;
+			if *count == 0 {
+				self.protected.remove(&f.head);
+			}
+		}
+	}
+
+	// sift automatically once more than threshold nodes are in the unique
+	// table at the end of an operation, None turns it off. Only the result
+	// of that operation and protected functions survive the sift, protect
+	// every other handle still in use before turning this on.
+	pub fn set_auto_reorder(&mut self, threshold: Option<usize>) {
+		self.auto_reorder = threshold;
+	}
+
+	// number of nodes in the unique table, dead nodes are only dropped by
+	// reordering and gc
+	pub fn live_node_count(&self) -> usize {
+		self.unique_table.len()
+	}
+
+	// exchange the vars at lvl and lvl + 1
+	pub fn swap_levels(&mut self, lvl: usize) {
+		assert!(lvl + 1 < self.level_map.num_vars(), "no level below {} to swap with", lvl);
+		let mut st = self.start_reorder();
+		self.swap(lvl, &mut st);
+	}
+
+	// Rudell's sifting: each var in turn, biggest level first, is moved
+	// through every level and left where the table was smallest. Declared
+	// groups are moved as one.
+	pub fn sift(&mut self) {
+		self.sift_blocks(false);
+	}
+
+	// Sifting that also merges a block with its neighbour once the two are
+	// found symmetric, so symmetric vars end up contiguous and move together
+	// for the rest of the pass
+	pub fn group_sift(&mut self) {
+		self.sift_blocks(true);
+	}
+
+	// Keep vars together while reordering, e.g. the bits of one bus. Members
+	// are gathered under the topmost one at the next reordering and keep
+	// their relative order from then on.
+	pub fn group(&mut self, vars: &[usize]) {
+		for &x in vars {
+			assert!(!self.groups.iter().any(|g| g.contains(&x)), "var {} is already in a group", x);
+		}
+		let mut vars = vars.to_vec();
+		vars.sort_unstable();
+		vars.dedup();
+		if vars.len() > 1 {
+			self.groups.push(vars);
+		}
+	}
+
+	// Try every arrangement of each k adjacent levels (or groups), keeping the
+	// smallest, and slide the window down until a whole pass gains nothing
+	pub fn window_permute(&mut self, k: usize) {
+		let mut st = self.start_reorder();
+		let blocks = self.make_blocks(&mut st);
+		// blocks from the root level down
+		let mut order = Vec::new();
+		let mut lvl = 0;
+		while lvl < self.level_map.num_vars() {
+			let b = blocks.block_of[self.level_map.var(lvl)];
+			order.push(b);
+			lvl += blocks.members[b].len();
+		}
+		let k = k.min(order.len());
+		if k < 2 {
+			return;
+		}
+		let changes = plain_changes(k);
+		loop {
+			let pass_start = self.unique_table.len();
+			for w in 0..=order.len() - k {
+				let mut best = (self.unique_table.len(), order[w..w + k].to_vec());
+				for &i in &changes {
+					self.swap_block_positions(w + i, &mut order, &blocks, &mut st);
+					if self.unique_table.len() < best.0 {
+						best = (self.unique_table.len(), order[w..w + k].to_vec());
+					}
+				}
+				for (i, b) in best.1.into_iter().enumerate() {
+					let mut p = order.iter().position(|&c| c == b).unwrap();
+					while p > w + i {
+						self.swap_block_positions(p - 1, &mut order, &blocks, &mut st);
+						p -= 1;
+					}
+				}
+			}
+			if self.unique_table.len() >= pass_start {
+				break;
+			}
+		}
+	}
+
+	// order with the fewest nodes for f and that size, see Bdd::optimal_order
+	pub fn optimal_order(&self, f: FunctionNode) -> (Vec<usize>, usize) {
+		self.export(f).optimal_order()
+	}
+
+	// called once the outermost operation is done with its result
+	pub(crate) fn maybe_reorder(&mut self, f: FunctionNode) {
+		if let Some(threshold) = self.auto_reorder {
+			if self.unique_table.len() > threshold {
+				self.protect(f);
+				self.sift();
+				self.unprotect(f);
+				// don't reorder again until the table has grown a good deal
+				self.auto_reorder = Some(threshold.max(2 * self.unique_table.len()));
+			}
+		}
+	}
+
+	fn sift_blocks(&mut self, merge_symmetric: bool) {
+		let n = self.level_map.num_vars();
+		if n < 2 {
+			return;
+		}
+		let mut st = self.start_reorder();
+		let mut blocks = self.make_blocks(&mut st);
+		let mut ids: Vec<usize> = (0..blocks.members.len()).collect();
+		let counts: Vec<usize> = blocks.members.iter()
+			.map(|vars| vars.iter().map(|&x| self.unique_table.var_len(x)).sum())
+			.collect();
+		ids.sort_by_key(|&b| std::cmp::Reverse(counts[b]));
+
+		for b in ids {
+			let len = blocks.members[b].len();
+			if len == 0 {
+				// merged into a symmetric neighbour
+				continue;
+			}
+			let start = self.block_top(&blocks, b);
+			let mut best = (self.unique_table.len(), start);
+			// nearer end first, so the long trip is only made once
+			if start < n - len - start {
+				self.sift_up(b, &mut best, &blocks, &mut st);
+				self.sift_down(b, &mut best, &blocks, &mut st);
+			} else {
+				self.sift_down(b, &mut best, &blocks, &mut st);
+				self.sift_up(b, &mut best, &blocks, &mut st);
+			}
+			while self.block_top(&blocks, b) > best.1 {
+				self.move_block_up(b, &blocks, &mut st);
+			}
+			while self.block_top(&blocks, b) < best.1 {
+				self.move_block_down(b, &blocks, &mut st);
+			}
+			if merge_symmetric {
+				self.merge_symmetric(b, &mut blocks, &mut st);
+			}
+		}
+	}
+
+	fn sift_up(&mut self, b: usize, best: &mut (usize, usize), blocks: &Blocks, st: &mut ReorderState) {
+		while self.block_top(blocks, b) > 0 {
+			self.move_block_up(b, blocks, st);
+			if !self.record_size(self.block_top(blocks, b), best) {
+				break;
+			}
+		}
+	}
+
+	fn sift_down(&mut self, b: usize, best: &mut (usize, usize), blocks: &Blocks, st: &mut ReorderState) {
+		while self.block_top(blocks, b) + blocks.members[b].len() < self.level_map.num_vars() {
+			self.move_block_down(b, blocks, st);
+			if !self.record_size(self.block_top(blocks, b), best) {
+				break;
+			}
+		}
+	}
+
+	// note the current size, false once it has grown too much to go on
+	fn record_size(&self, lvl: usize, best: &mut (usize, usize)) -> bool {
+		let size = self.unique_table.len();
+		if size < best.0 {
+			*best = (size, lvl);
+		}
+		(size as f64) <= MAX_GROWTH * best.0 as f64
+	}
+
+	// Absorb neighbouring blocks into b for as long as the vars on either
+	// side of its boundary are symmetric
+	fn merge_symmetric(&mut self, b: usize, blocks: &mut Blocks, st: &mut ReorderState) {
+		loop {
+			let top = self.block_top(blocks, b);
+			let bottom = top + blocks.members[b].len() - 1;
+			let other = if bottom + 1 < self.level_map.num_vars() && self.symmetric(bottom, st) {
+				blocks.block_of[self.level_map.var(bottom + 1)]
+			} else if top > 0 && self.symmetric(top - 1, st) {
+				blocks.block_of[self.level_map.var(top - 1)]
+			} else {
+				return;
+			};
+			let members = std::mem::take(&mut blocks.members[other]);
+			for &x in &members {
+				blocks.block_of[x] = b;
+			}
+			if self.level_map.level(members[0]) < top {
+				// other sat right above b
+				let below = std::mem::replace(&mut blocks.members[b], members);
+				blocks.members[b].extend(below);
+			} else {
+				blocks.members[b].extend(members);
+			}
+		}
+	}
+
+	// Drop everything not reachable from a protected root, so the unique table
+	// only holds live nodes, and count the references to each of them.
+	fn start_reorder(&mut self) -> ReorderState {
+		// results may point at nodes about to be freed or rewritten
+		self.computed_table.clear();
+		let mut st = ReorderState {
+			refs: vec![0; self.nodes.len()],
+		};
+		let mut stack = Vec::new();
+		for (&n, &count) in &self.protected {
+			st.refs[n] += count;
+			stack.push(n);
+		}
+		let mut visited = vec![false; self.nodes.len()];
+		while let Some(n) = stack.pop() {
+			if visited[n] {
+				continue;
+			}
+			visited[n] = true;
+			let node = &self.nodes[n];
+			for &child in &[node.t_arc, node.e_arc] {
+				if child != term {
+					st.refs[child] += 1;
+					stack.push(child);
+				}
+			}
+		}
+		let mut dropped = Vec::new();
+		self.unique_table.retain(|n| {
+			if !visited[n] {
+				dropped.push(n);
+			}
+			visited[n]
+		});
+		self.free.extend(dropped);
+		st
+	}
+
+	// Gather every declared group under its topmost member and split the
+	// levels into blocks: one per group, one per ungrouped var
+	fn make_blocks(&mut self, st: &mut ReorderState) -> Blocks {
+		let n = self.level_map.num_vars();
+		let mut blocks = Blocks {
+			members: Vec::new(),
+			block_of: vec![n; n],
+		};
+		for group in self.groups.clone() {
+			let mut vars = group;
+			vars.sort_by_key(|&x| self.level_map.level(x));
+			let top = self.level_map.level(vars[0]);
+			// moving a var up through a gathered group keeps that group whole
+			for (i, &x) in vars.iter().enumerate() {
+				while self.level_map.level(x) > top + i {
+					self.swap(self.level_map.level(x) - 1, st);
+				}
+			}
+			for &x in &vars {
+				blocks.block_of[x] = blocks.members.len();
+			}
+			blocks.members.push(vars);
+		}
+		for x in 0..n {
+			if blocks.block_of[x] == n {
+				blocks.block_of[x] = blocks.members.len();
+				blocks.members.push(vec![x]);
+			}
+		}
+		blocks
+	}
+
+	fn block_top(&self, blocks: &Blocks, b: usize) -> usize {
+		self.level_map.level(blocks.members[b][0])
+	}
+
+	fn move_block_up(&mut self, b: usize, blocks: &Blocks, st: &mut ReorderState) {
+		let above = blocks.block_of[self.level_map.var(self.block_top(blocks, b) - 1)];
+		let top = self.block_top(blocks, above);
+		self.swap_blocks(top, blocks.members[above].len(), blocks.members[b].len(), st);
+	}
+
+	fn move_block_down(&mut self, b: usize, blocks: &Blocks, st: &mut ReorderState) {
+		let top = self.block_top(blocks, b);
+		let len = blocks.members[b].len();
+		let below = blocks.block_of[self.level_map.var(top + len)];
+		self.swap_blocks(top, len, blocks.members[below].len(), st);
+	}
+
+	// swap the blocks at positions p and p + 1 of order
+	fn swap_block_positions(&mut self, p: usize, order: &mut [usize], blocks: &Blocks, st: &mut ReorderState) {
+		let (upper, lower) = (order[p], order[p + 1]);
+		let top = self.block_top(blocks, upper);
+		self.swap_blocks(top, blocks.members[upper].len(), blocks.members[lower].len(), st);
+		order.swap(p, p + 1);
+	}
+
+	// Move the upper_len levels starting at top below the lower_len levels
+	// after them, one var at a time from the bottom of the upper block
+	fn swap_blocks(&mut self, top: usize, upper_len: usize, lower_len: usize, st: &mut ReorderState) {
+		for i in (0..upper_len).rev() {
+			for j in 0..lower_len {
+				self.swap(top + i + j, st);
+			}
+		}
+	}
+
+	// Are the vars at lvl and lvl + 1 symmetric in every live function: each
+	// node of the upper var has equal (1, 0) and (0, 1) cofactors, and the
+	// lower var is only ever reached through the upper one
+	fn symmetric(&self, lvl: usize, st: &ReorderState) -> bool {
+		let x = self.level_map.var(lvl);
+		let y = self.level_map.var(lvl + 1);
+		let mut arcs = 0;
+		for n in self.unique_table.var_nodes(x) {
+			let node = &self.nodes[n];
+			let (_, f10) = self.y_cofactors(func(node.t_arc, false), y);
+			let (f01, _) = self.y_cofactors(func(node.e_arc, node.e_complement), y);
+			if f10 != f01 {
+				return false;
+			}
+			arcs += [node.t_arc, node.e_arc].iter()
+				.filter(|&&c| c != term && self.nodes[c].label == y)
+				.count();
+		}
+		let y_refs: usize = self.unique_table.var_nodes(y).iter().map(|&n| st.refs[n]).sum();
+		arcs == y_refs
+	}
+
+	// Swap the var x at lvl with the var y at lvl + 1.
+	// Nodes of x that don't depend on y just move down a level. The others are
+	// relabeled y in place, with new x nodes built below them from the four
+	// grandchild cofactors, so every parent arc stays correct.
+	fn swap(&mut self, lvl: usize, st: &mut ReorderState) {
+		let x = self.level_map.var(lvl);
+		let y = self.level_map.var(lvl + 1);
+		let is_y = |c: NodeIdx| c != term && self.nodes[c].label == y;
+		let moved: Vec<NodeIdx> = self.unique_table.var_nodes(x).into_iter()
+			.filter(|&n| is_y(self.nodes[n].t_arc) || is_y(self.nodes[n].e_arc))
+			.collect();
+
+		for n in moved {
+			let node = self.nodes[n].clone();
+			let t = func(node.t_arc, false);
+			let e = func(node.e_arc, node.e_complement);
+			let (f11, f10) = self.y_cofactors(t, y);
+			let (f01, f00) = self.y_cofactors(e, y);
+			let new_t = self.insert_ref(x, f11, f01, st);
+			let new_e = self.insert_ref(x, f10, f00, st);
+
+			self.unique_table.remove(&node);
+			let new_node = InternalNode {
+				label: y,
+				t_arc: new_t.head,
+				e_arc: new_e.head,
+				e_complement: new_e.complement,
+			};
+			self.unique_table.insert(&new_node, n);
+			self.nodes[n] = new_node;
+			self.deref(node.t_arc, st);
+			self.deref(node.e_arc, st);
+		}
+
+		self.level_map.swap(lvl);
+	}
+
+	// cofactors of f by y, f sits at y's level or below
+	fn y_cofactors(&self, f: FunctionNode, y: usize) -> (FunctionNode, FunctionNode) {
+		if f.head != term && self.nodes[f.head].label == y {
+			let node = &self.nodes[f.head];
+			(func(node.t_arc, f.complement), func(node.e_arc, node.e_complement ^ f.complement))
+		} else {
+			(f, f)
+		}
+	}
+
+	// unique_insert that keeps reference counts up to date
+	fn insert_ref(&mut self, x: usize, pos_cof: FunctionNode, neg_cof: FunctionNode, st: &mut ReorderState) -> FunctionNode {
+		let table_len = self.unique_table.len();
+		let f = self.unique_insert(x, pos_cof, neg_cof);
+		if self.unique_table.len() > table_len {
+			// either a fresh slot or a freed one, whose count is back at 0
+			if f.head >= st.refs.len() {
+				st.refs.resize(f.head + 1, 0);
+			}
+			let node = &self.nodes[f.head];
+			for &child in &[node.t_arc, node.e_arc] {
+				if child != term {
+					st.refs[child] += 1;
+				}
+			}
+		}
+		if f.head != term {
+			st.refs[f.head] += 1;
+		}
+		f
+	}
+
+	// drop a reference to n, freeing it and whatever only it kept alive
+	fn deref(&mut self, n: NodeIdx, st: &mut ReorderState) {
+		let mut stack = vec![n];
+		while let Some(n) = stack.pop() {
+			if n == term {
+				continue;
+			}
+			st.refs[n] -= 1;
+			if st.refs[n] == 0 {
+				let node = &self.nodes[n];
+				self.unique_table.remove(node);
+				stack.push(node.t_arc);
+				stack.push(node.e_arc);
+				self.free.push(n);
+			}
+		}
+	}
+}
+
+// Steinhaus-Johnson-Trotter: positions i of adjacent swaps (i, i + 1) that
+// step through all k! arrangements of k items, k! - 1 swaps in all
+fn plain_changes(k: usize) -> Vec<usize> {
+	if k < 2 {
+		return Vec::new();
+	}
+	let sub = plain_changes(k - 1);
+	let mut swaps = Vec::new();
+	// the last item sweeps across the others, which take one step of the
+	// smaller sequence between sweeps
+	for j in 0..=sub.len() {
+		let leftward = j % 2 == 0;
+		if leftward {
+			swaps.extend((0..k - 1).rev());
+		} else {
+			swaps.extend(0..k - 1);
+		}
+		if j < sub.len() {
+			// the last item sits at the left end after a leftward sweep
+			swaps.push(sub[j] + if leftward { 1 } else { 0 });
+		}
+	}
+	swaps
+}
+
+// Exact variable ordering
+impl Bdd {
+
+	// Friedman-Supowit: an order with the fewest nodes, and that size.
+	// How many nodes a var gets only depends on which vars sit below it, not
+	// on their order, so the best order of each set of bottom vars is found
+	// from the best orders of its subsets. Only the support is searched, the
+	// other vars keep their relative order below it. Time and memory grow
+	// like 3^n in the size n of the support.
+	pub fn optimal_order(&self) -> (Vec<usize>, usize) {
+		let mut in_support = vec![false; self.var_ord.len()];
+		for node in &self.nodes {
+			in_support[node.label] = true;
+		}
+		let support: Vec<usize> = self.var_ord.iter().cloned().filter(|&x| in_support[x]).collect();
+		let n = support.len();
+		assert!(n <= MAX_EXACT_VARS, "exact ordering of {} vars is out of reach", n);
+		// truth table over the support, support[i] is bit i of the index.
+		// Entries are subfunction ids shifted left once, with the low bit
+		// for complement.
+		let table: Vec<u32> = (0..1usize << n)
+			.map(|a| {
+				let mut env: Env = vec![false; self.var_ord.len()];
+				for (i, &x) in support.iter().enumerate() {
+					env[x] = (a >> i) & 1 == 1;
+				}
+				if self.eval(&env) { 0 } else { 1 }
+			})
+			.collect();
+
+		// best size and top var of each set of bottom vars
+		let mut cost = vec![usize::MAX; 1 << n];
+		let mut top = vec![0; 1 << n];
+		cost[0] = 0;
+		let mut layer = BTreeMap::new();
+		layer.insert(0usize, table);
+		for _ in 0..n {
+			let mut next = BTreeMap::new();
+			for (&below, table) in &layer {
+				for i in (0..n).filter(|&i| (below >> i) & 1 == 0) {
+					// position of var i among the vars still in the table
+					let p = (!below & ((1 << i) - 1)).count_ones() as usize;
+					let (compacted, nodes) = Bdd::absorb_var(table, p);
+					let set = below | (1 << i);
+					if cost[below] + nodes < cost[set] {
+						cost[set] = cost[below] + nodes;
+						top[set] = i;
+					}
+					// every order of the set leaves the same subfunctions
+					next.entry(set).or_insert(compacted);
+				}
+			}
+			layer = next;
+		}
+
+		let mut ord = Vec::with_capacity(self.var_ord.len());
+		let mut set = (1 << n) - 1;
+		while set != 0 {
+			ord.push(support[top[set]]);
+			set &= !(1 << top[set]);
+		}
+		ord.extend(self.var_ord.iter().cloned().filter(|&x| !in_support[x]));
+		(ord, cost[(1 << n) - 1])
+	}
+
+	// Move the var at position p of table under the subfunctions, giving the
+	// table over the remaining vars and how many nodes the var needed
+	fn absorb_var(table: &[u32], p: usize) -> (Vec<u32>, usize) {
+		let next_id = table.iter().max().map_or(0, |m| (m >> 1) + 1);
+		let low = (1 << p) - 1;
+		let mut unique = HashMap::new();
+		let compacted = (0..table.len() / 2)
+			.map(|a| {
+				let i = ((a & !low) << 1) | (a & low);
+				let (hi, lo) = (table[i | (1 << p)], table[i]);
+				if hi == lo {
+					hi
+				} else {
+					// then arc kept regular, same as unique_insert_hash
+					let c = hi & 1;
+					let fresh = next_id + unique.len() as u32;
+					(*unique.entry((hi ^ c, lo ^ c)).or_insert(fresh) << 1) | c
+				}
+			})
+			.collect();
+		(compacted, unique.len())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::plain_changes;
+	use crate::expr::*;
+	use crate::manager::Manager;
+	use crate::neg_arc_bdd::from;
+	use crate::rng::SplitMix64;
+	use crate::test_util::*;
+	use Expr::*;
+	use BOp::*;
+
+	#[test]
+	fn swap_levels_keeps_functions() {
+		let es = exprs();
+		let (mut m, fs) = fresh(&es, &bad_order());
+		for lvl in (0..NUM_VARS - 1).chain((0..NUM_VARS - 1).rev()).chain([2, 0, 3, 1, 4, 2]) {
+			m.swap_levels(lvl);
+			check(&m, &es, &fs);
+		}
+	}
+
+	#[test]
+	fn sift_keeps_functions() {
+		let es = exprs();
+		let (mut m, fs) = fresh(&es, &bad_order());
+		let before = m.live_node_count();
+		m.sift();
+		check(&m, &es, &fs);
+		assert_ne!(m.var_ord(), &bad_order()[..]);
+		assert!(m.live_node_count() < before);
+	}
+
+	#[test]
+	fn group_sift_keeps_functions() {
+		let es = exprs();
+		let (mut m, fs) = fresh(&es, &bad_order());
+		m.group_sift();
+		check(&m, &es, &fs);
+	}
+
+	#[test]
+	fn grouped_sift_keeps_groups_together() {
+		let es = exprs();
+		let (mut m, fs) = fresh(&es, &bad_order());
+		m.group(&[1, 4]);
+		m.sift();
+		check(&m, &es, &fs);
+		let ord = m.var_ord();
+		let p = ord.iter().position(|&x| x == 1).unwrap();
+		let q = ord.iter().position(|&x| x == 4).unwrap();
+		assert_eq!(p.max(q) - p.min(q), 1);
+	}
+
+	#[test]
+	fn window_permute_keeps_functions() {
+		let es = exprs();
+		let (mut m, fs) = fresh(&es, &bad_order());
+		m.window_permute(3);
+		check(&m, &es, &fs);
+	}
+
+	#[test]
+	fn optimal_order_matches_every_permutation() {
+		let mut rng = SplitMix64::new(18);
+		for _ in 0..40 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let e = rand_expr(&mut rng, nv, 5);
+			let var_ord = rand_ord(&mut rng, nv);
+			let mut m = Manager::new(&var_ord);
+			let f = m.from_expr(&e);
+			let (ord, size) = m.optimal_order(f);
+			assert_eq!(from(&e, &ord).size(), size);
+
+			// smallest over all nv! orders
+			let mut perm: Vec<usize> = (0..nv).collect();
+			let mut best = from(&e, &perm).size();
+			for i in plain_changes(nv) {
+				perm.swap(i, i + 1);
+				best = best.min(from(&e, &perm).size());
+			}
+			assert_eq!(size, best);
+
+			// vars outside the support go below it in their old order
+			let support = m.support(f);
+			assert_eq!(ord.len(), nv);
+			let mut searched = ord[..support.len()].to_vec();
+			searched.sort_unstable();
+			let mut expected = support.clone();
+			expected.sort_unstable();
+			assert_eq!(searched, expected);
+			let rest: Vec<usize> = var_ord.iter().cloned().filter(|x| !support.contains(x)).collect();
+			assert_eq!(&ord[support.len()..], &rest[..]);
+		}
+	}
+
+	#[test]
+	fn optimal_order_searches_only_the_support() {
+		// 40 vars in all, far too many to search, but only 4 in the support
+		let e = bin(bin(Var(3), And, Var(17)), Or, bin(Var(25), And, Var(38)));
+		let mut m = Manager::new(&(0..40).collect::<Vec<usize>>());
+		let f = m.from_expr(&e);
+		let (ord, size) = m.optimal_order(f);
+		assert_eq!(size, 4);
+		assert_eq!(ord.len(), 40);
+		let rest: Vec<usize> = (0..40).filter(|x| ![3, 17, 25, 38].contains(x)).collect();
+		assert_eq!(&ord[4..], &rest[..]);
+	}
+
+	#[test]
+	fn reordered_manager_keeps_building() {
+		let es = exprs();
+		let (mut m, mut fs) = fresh(&es, &bad_order());
+		m.sift();
+		let e = bin(Var(5), Or, not(Var(2)));
+		let f = m.from_expr(&e);
+		m.protect(f);
+		fs.push(f);
+		let mut es = es;
+		es.push(e);
+		m.gc();
+		check(&m, &es, &fs);
+		m.window_permute(2);
+		check(&m, &es, &fs);
+		// every slot is either in the table or free to reuse
+		assert_eq!(m.nodes.len(), m.live_node_count() + m.free.len());
+	}
+
+	#[test]
+	fn auto_reorder_keeps_protected_handles() {
+		let mut es = exprs();
+		es.push(Var(0));
+		let mut m = Manager::new(&bad_order());
+		m.set_auto_reorder(Some(10));
+		let fs = build(&mut m, &es);
+		assert_ne!(m.var_ord(), &bad_order()[..]);
+		// same function, same node
+		let x0 = *fs.last().unwrap();
+		assert_eq!(m.var(0), x0);
+		// slots freed by the sift are reused rather than leaked
+		assert_eq!(m.nodes.len(), m.live_node_count() + m.free.len());
+		m.gc();
+		check(&m, &es, &fs);
+	}
+}
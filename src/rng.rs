@@ -0,0 +1,95 @@
+use crate::big_uint::BigUint;
+
+// Source of random bits for sampling, implement it to plug in another
+// generator. Same seed, same draws.
+pub trait Rng {
+	fn next_u64(&mut self) -> u64;
+
+	fn next_bool(&mut self) -> bool {
+		self.next_u64() >> 63 == 1
+	}
+
+	// uniform in [0, bound), bound must not be zero
+	fn below(&mut self, bound: &BigUint) -> BigUint {
+		assert!(!bound.is_zero(), "empty range");
+		let bits = bound.bits();
+		loop {
+			// rejection sample among the numbers with as many bits as bound
+			let len = bits.div_ceil(32);
+			let mut digits = Vec::with_capacity(len);
+			for i in 0..len {
+				let mut d = self.next_u64() as u32;
+				let rem_bits = bits - 32 * i;
+				if rem_bits < 32 {
+					d &= (1 << rem_bits) - 1;
+				}
+				digits.push(d);
+			}
+			let r = BigUint::from_digits(digits);
+			if r < *bound {
+				return r;
+			}
+		}
+	}
+}
+
+// splitmix64, small and fast with good enough statistics for stimulus
+#[derive(Clone, Debug)]
+pub struct SplitMix64 {
+	state: u64,
+}
+
+impl SplitMix64 {
+	pub fn new(seed: u64) -> SplitMix64 {
+		SplitMix64 { state: seed }
+	}
+}
+
+impl Rng for SplitMix64 {
+	fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+		z ^ (z >> 31)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn same_seed_same_draws() {
+		// first output of the reference splitmix64 from seed 0
+		assert_eq!(SplitMix64::new(0).next_u64(), 0xe220a8397b1dcdaf);
+		let mut a = SplitMix64::new(42);
+		let mut b = SplitMix64::new(42);
+		let mut c = SplitMix64::new(43);
+		let xs: Vec<u64> = (0..100).map(|_| a.next_u64()).collect();
+		let ys: Vec<u64> = (0..100).map(|_| b.next_u64()).collect();
+		let zs: Vec<u64> = (0..100).map(|_| c.next_u64()).collect();
+		assert_eq!(xs, ys);
+		assert_ne!(xs, zs);
+	}
+
+	#[test]
+	fn below_stays_in_range() {
+		let mut rng = SplitMix64::new(7);
+		for n in [1u64, 2, 3, 5, 7, 8, 100] {
+			let bound = BigUint::from(n);
+			let mut seen = vec![false; n as usize];
+			for _ in 0..50 * n {
+				let r = rng.below(&bound);
+				assert!(r < bound);
+				seen[r.to_string().parse::<usize>().unwrap()] = true;
+			}
+			assert!(seen.iter().all(|&s| s));
+		}
+		// bounds past one digit
+		let bound = BigUint::pow2(100).add(&BigUint::from(3));
+		for _ in 0..100 {
+			assert!(rng.below(&bound) < bound);
+		}
+	}
+}
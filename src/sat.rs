@@ -0,0 +1,612 @@
+use std::collections::HashMap;
+
+use crate::big_uint::BigUint;
+use crate::expr::*;
+use crate::neg_arc_bdd::*;
+use crate::rng::Rng;
+
+// Weights combined by weighted_count, add for disjoint branches and mul for
+// a literal with what lies under it
+pub trait Semiring: Clone {
+	fn zero() -> Self;
+	fn one() -> Self;
+	fn add(&self, other: &Self) -> Self;
+	fn mul(&self, other: &Self) -> Self;
+}
+
+impl Semiring for f64 {
+	fn zero() -> f64 { 0.0 }
+	fn one() -> f64 { 1.0 }
+	fn add(&self, other: &f64) -> f64 { self + other }
+	fn mul(&self, other: &f64) -> f64 { self * other }
+}
+
+impl Semiring for BigUint {
+	fn zero() -> BigUint { BigUint::zero() }
+	fn one() -> BigUint { BigUint::one() }
+	fn add(&self, other: &BigUint) -> BigUint { BigUint::add(self, other) }
+	fn mul(&self, other: &BigUint) -> BigUint { BigUint::mul(self, other) }
+}
+
+// min-plus semiring, a weighted count over it is the cost of the cheapest
+// satisfying assignment
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct MinCost(pub f64);
+
+impl Semiring for MinCost {
+	fn zero() -> MinCost { MinCost(f64::INFINITY) }
+	fn one() -> MinCost { MinCost(0.0) }
+	fn add(&self, other: &MinCost) -> MinCost { MinCost(self.0.min(other.0)) }
+	fn mul(&self, other: &MinCost) -> MinCost { MinCost(self.0 + other.0) }
+}
+
+// Counting and extracting the satisfying assignments of a built bdd
+impl Bdd {
+
+	// number of assignments to num_vars vars satisfying the bdd,
+	// vars past the end of var_ord are don't cares. num_vars can't be less
+	// than the vars in var_ord, the count would be over a different space.
+	pub fn sat_count(&self, num_vars: usize) -> BigUint {
+		assert!(num_vars >= self.var_ord.len(), "sat_count over fewer vars than the bdd is ordered over");
+		let levels = self.level_map();
+		let mut counts = vec![None; self.nodes.len()];
+		let count = self.sat_count_rec(self.f.head, &levels, &mut counts);
+		let lvl = self.node_level(self.f.head, &levels);
+		// counts are over the levels from the node down, fill in the rest
+		let count = if self.f.complement {
+			BigUint::pow2(self.var_ord.len() - lvl).sub(&count)
+		} else {
+			count
+		}.shl(lvl);
+		count.shl(num_vars - self.var_ord.len())
+	}
+
+	// probability the bdd is true when each var x is independently true
+	// with probability p[x]
+	pub fn probability(&self, p: &[f64]) -> f64 {
+		let weights: Vec<(f64, f64)> = p.iter().map(|&p_x| (p_x, 1.0 - p_x)).collect();
+		self.weighted_count(&weights)
+	}
+
+	// sum over satisfying assignments of the product of literal weights,
+	// weights[x] is (weight of x, weight of !x)
+	pub fn weighted_count<S: Semiring>(&self, weights: &[(S, S)]) -> S {
+		let levels = self.level_map();
+		let mut memo = vec![None; self.nodes.len()];
+		let (pos, neg) = self.weighted_count_rec(self.f.head, weights, &levels, &mut memo);
+		let count = if self.f.complement { neg } else { pos };
+		let lvl = self.node_level(self.f.head, &levels);
+		self.skipped_weight(0, lvl, weights).mul(&count)
+	}
+
+	// cheapest satisfying assignment and its cost, costs[x] is
+	// (cost of x, cost of !x). Found as the shortest path to true.
+	pub fn min_cost_sat(&self, costs: &[(f64, f64)]) -> Option<(Env, f64)> {
+		let weights: Vec<(MinCost, MinCost)> = costs.iter().map(|&(c_x, c_nx)| (MinCost(c_x), MinCost(c_nx))).collect();
+		let levels = self.level_map();
+		let mut memo = vec![None; self.nodes.len()];
+		self.weighted_count_rec(self.f.head, &weights, &levels, &mut memo);
+		let edge_cost = |f: FunctionNode| -> MinCost {
+			let (pos, neg) = if f.head == term {
+				(MinCost::one(), MinCost::zero())
+			} else {
+				memo[f.head].unwrap()
+			};
+			if f.complement { neg } else { pos }
+		};
+
+		if edge_cost(self.f).0.is_infinite() {
+			return None;
+		}
+
+		let mut env = vec![false; self.var_ord.len()];
+		// skipped levels take their cheaper literal
+		let cheapest = |env: &mut Env, from_lvl: usize, to_lvl: usize| {
+			for lvl in from_lvl..to_lvl {
+				let x = self.var_ord[lvl];
+				env[x] = costs[x].0 < costs[x].1;
+			}
+		};
+		let mut f = self.f;
+		let mut lvl = self.node_level(f.head, &levels);
+		let cost = self.skipped_weight(0, lvl, &weights).mul(&edge_cost(f));
+		cheapest(&mut env, 0, lvl);
+		// follow the cheaper branch down to true
+		while f.head != term {
+			let node = &self.nodes[f.head];
+			let t = func(node.t_arc, f.complement);
+			let e = func(node.e_arc, f.complement ^ node.e_complement);
+			let t_lvl = self.node_level(t.head, &levels);
+			let e_lvl = self.node_level(e.head, &levels);
+			let t_cost = weights[node.label].0.mul(&self.skipped_weight(lvl + 1, t_lvl, &weights)).mul(&edge_cost(t));
+			let e_cost = weights[node.label].1.mul(&self.skipped_weight(lvl + 1, e_lvl, &weights)).mul(&edge_cost(e));
+			let take_t = t_cost <= e_cost;
+			env[node.label] = take_t;
+			let (next, next_lvl) = if take_t { (t, t_lvl) } else { (e, e_lvl) };
+			cheapest(&mut env, lvl + 1, next_lvl);
+			f = next;
+			lvl = next_lvl;
+		}
+		Some((env, cost.0))
+	}
+
+	// weighted counts of the node and of its complement, over the levels
+	// from the node down. Keeping both polarities means complement arcs
+	// never need a subtraction, which a semiring does not have.
+	fn weighted_count_rec<S: Semiring>(&self, n: NodeIdx, weights: &[(S, S)], levels: &LevelMap, memo: &mut Vec<Option<(S, S)>>) -> (S, S) {
+		if n == term {
+			return (S::one(), S::zero());
+		}
+		if let Some(counts) = &memo[n] {
+			return counts.clone();
+		}
+		let node = &self.nodes[n];
+		let lvl = levels.level(node.label);
+		let (w_x, w_nx) = &weights[node.label];
+		let (t_pos, t_neg) = self.weighted_count_rec(node.t_arc, weights, levels, memo);
+		let (mut e_pos, mut e_neg) = self.weighted_count_rec(node.e_arc, weights, levels, memo);
+		if node.e_complement {
+			std::mem::swap(&mut e_pos, &mut e_neg);
+		}
+		let t_skip = self.skipped_weight(lvl + 1, self.node_level(node.t_arc, levels), weights);
+		let e_skip = self.skipped_weight(lvl + 1, self.node_level(node.e_arc, levels), weights);
+		let t_x = w_x.mul(&t_skip);
+		let e_x = w_nx.mul(&e_skip);
+		let counts = (
+			t_x.mul(&t_pos).add(&e_x.mul(&e_pos)),
+			t_x.mul(&t_neg).add(&e_x.mul(&e_neg)),
+		);
+		memo[n] = Some(counts.clone());
+		counts
+	}
+
+	// skipped levels can take either value
+	fn skipped_weight<S: Semiring>(&self, from_lvl: usize, to_lvl: usize, weights: &[(S, S)]) -> S {
+		(from_lvl..to_lvl).fold(S::one(), |acc, lvl| {
+			let (w_x, w_nx) = &weights[self.var_ord[lvl]];
+			acc.mul(&w_x.add(w_nx))
+		})
+	}
+
+	// one satisfying cube, vars left out are don't cares
+	pub fn any_sat(&self) -> Option<PartialEnv> {
+		let mut f = self.f;
+		let mut asgn: PartialEnv = HashMap::new();
+		// every internal node is a non constant function, so any edge that
+		// is not straight to false leads to a satisfying path
+		while f.head != term {
+			let node = &self.nodes[f.head];
+			let t = func(node.t_arc, f.complement);
+			let take_t = !(t.head == term && t.complement);
+			asgn.insert(node.label, take_t);
+			f = if take_t { t } else { func(node.e_arc, f.complement ^ node.e_complement) };
+		}
+		if f.complement { None } else { Some(asgn) }
+	}
+
+	// lazily walk every path to true, one disjoint cube per path
+	pub fn sat_cubes(&self) -> SatCubes<'_> {
+		SatCubes {
+			bdd: self,
+			stack: vec![(self.f, 0, None)],
+			path: Vec::new(),
+		}
+	}
+
+	// lazily expand sat_cubes into full assignments over var_ord
+	pub fn sat_minterms(&self) -> SatMinterms<'_> {
+		SatMinterms {
+			cubes: self.sat_cubes(),
+			env: Vec::new(),
+			free: Vec::new(),
+			done: true,
+		}
+	}
+
+	// one satisfying assignment drawn uniformly at random, None if unsat
+	pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<Env> {
+		self.sampler().sample(rng)
+	}
+
+	// counts every node once so repeated draws are cheap
+	pub fn sampler(&self) -> SatSampler<'_> {
+		let levels = self.level_map();
+		let mut counts = vec![None; self.nodes.len()];
+		self.sat_count_rec(self.f.head, &levels, &mut counts);
+		SatSampler {
+			bdd: self,
+			levels,
+			counts,
+		}
+	}
+
+	// satisfying assignments of the uncomplemented node over the levels
+	// from its own level to the terminal
+	fn sat_count_rec(&self, n: NodeIdx, levels: &LevelMap, counts: &mut Vec<Option<BigUint>>) -> BigUint {
+		if n == term {
+			return BigUint::one();
+		}
+		if let Some(count) = &counts[n] {
+			return count.clone();
+		}
+		let node = &self.nodes[n];
+		let lvl = levels.level(node.label);
+		let t_lvl = self.node_level(node.t_arc, levels);
+		let e_lvl = self.node_level(node.e_arc, levels);
+		let t_count = self.sat_count_rec(node.t_arc, levels, counts);
+		let mut e_count = self.sat_count_rec(node.e_arc, levels, counts);
+		if node.e_complement {
+			e_count = BigUint::pow2(self.var_ord.len() - e_lvl).sub(&e_count);
+		}
+		// every skipped level doubles the count
+		let count = t_count.shl(t_lvl - lvl - 1).add(&e_count.shl(e_lvl - lvl - 1));
+		counts[n] = Some(count.clone());
+		count
+	}
+
+	// both directions of the order the bdd was built with
+	pub(crate) fn level_map(&self) -> LevelMap {
+		LevelMap::new(&self.var_ord)
+	}
+
+	// terminal sits below every variable
+	pub(crate) fn node_level(&self, n: NodeIdx, levels: &LevelMap) -> usize {
+		levels.node_level(n, &self.nodes)
+	}
+}
+
+// var and the value it is fixed to along a path
+type Literal = (usize, bool);
+
+pub struct SatCubes<'a> {
+	bdd: &'a Bdd,
+	// edges still to visit, with the path length and literal leading to them
+	stack: Vec<(FunctionNode, usize, Option<Literal>)>,
+	path: Vec<Literal>,
+}
+
+impl<'a> Iterator for SatCubes<'a> {
+	type Item = PartialEnv;
+
+	fn next(&mut self) -> Option<PartialEnv> {
+		while let Some((f, len, lit)) = self.stack.pop() {
+			self.path.truncate(len);
+			self.path.extend(lit);
+			if f.head == term {
+				if !f.complement {
+					return Some(self.path.iter().cloned().collect());
+				}
+			} else {
+				let node = &self.bdd.nodes[f.head];
+				let t = func(node.t_arc, f.complement);
+				let e = func(node.e_arc, f.complement ^ node.e_complement);
+				let len = self.path.len();
+				// then branch first
+				self.stack.push((e, len, Some((node.label, false))));
+				self.stack.push((t, len, Some((node.label, true))));
+			}
+		}
+		None
+	}
+}
+
+pub struct SatMinterms<'a> {
+	cubes: SatCubes<'a>,
+	env: Env,
+	// don't care vars of the current cube
+	free: Vec<usize>,
+	// current cube has been fully expanded
+	done: bool,
+}
+
+impl<'a> Iterator for SatMinterms<'a> {
+	type Item = Env;
+
+	fn next(&mut self) -> Option<Env> {
+		if self.done {
+			let cube = self.cubes.next()?;
+			let num_vars = self.cubes.bdd.var_ord.len();
+			self.env = vec![false; num_vars];
+			self.free.clear();
+			for x in 0..num_vars {
+				match cube.get(&x) {
+					Some(b) => self.env[x] = *b,
+					None => self.free.push(x),
+				}
+			}
+			self.done = false;
+			return Some(self.env.clone());
+		}
+		// count up through the don't cares
+		match self.free.iter().position(|&x| !self.env[x]) {
+			Some(i) => {
+				for &x in &self.free[..i] {
+					self.env[x] = false;
+				}
+				self.env[self.free[i]] = true;
+				Some(self.env.clone())
+			},
+			None => {
+				self.done = true;
+				self.next()
+			}
+		}
+	}
+}
+
+// Uniform sampler over the satisfying assignments of a bdd, built on the
+// per node model counts
+pub struct SatSampler<'a> {
+	bdd: &'a Bdd,
+	levels: LevelMap,
+	counts: Vec<Option<BigUint>>,
+}
+
+impl<'a> SatSampler<'a> {
+
+	pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<Env> {
+		let bdd = self.bdd;
+		let mut f = bdd.f;
+		if self.edge_count(f).is_zero() {
+			return None;
+		}
+		let mut env = vec![false; bdd.var_ord.len()];
+		// vars above the root are don't cares
+		let mut lvl = bdd.node_level(f.head, &self.levels);
+		self.fill_free(&mut env, 0, lvl, rng);
+		while f.head != term {
+			let node = &bdd.nodes[f.head];
+			let t = func(node.t_arc, f.complement);
+			let e = func(node.e_arc, f.complement ^ node.e_complement);
+			let t_lvl = bdd.node_level(t.head, &self.levels);
+			let e_lvl = bdd.node_level(e.head, &self.levels);
+			// pick a branch weighted by how many assignments lie under it
+			let t_count = self.edge_count(t).shl(t_lvl - lvl - 1);
+			let e_count = self.edge_count(e).shl(e_lvl - lvl - 1);
+			let take_t = rng.below(&t_count.add(&e_count)) < t_count;
+			env[node.label] = take_t;
+			let (next, next_lvl) = if take_t { (t, t_lvl) } else { (e, e_lvl) };
+			self.fill_free(&mut env, lvl + 1, next_lvl, rng);
+			f = next;
+			lvl = next_lvl;
+		}
+		Some(env)
+	}
+
+	// satisfying assignments of f over the levels from f down
+	fn edge_count(&self, f: FunctionNode) -> BigUint {
+		let count = if f.head == term {
+			BigUint::one()
+		} else {
+			self.counts[f.head].clone().unwrap()
+		};
+		if f.complement {
+			let lvl = self.bdd.node_level(f.head, &self.levels);
+			BigUint::pow2(self.bdd.var_ord.len() - lvl).sub(&count)
+		} else {
+			count
+		}
+	}
+
+	// skipped levels can take either value
+	fn fill_free<R: Rng>(&self, env: &mut Env, from_lvl: usize, to_lvl: usize, rng: &mut R) {
+		for lvl in from_lvl..to_lvl {
+			env[self.bdd.var_ord[lvl]] = rng.next_bool();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::manager::Manager;
+	use crate::rng::SplitMix64;
+	use crate::test_util::*;
+	use Expr::*;
+	use BOp::*;
+
+	// assignments to num_vars vars satisfying e
+	fn count(e: &Expr, num_vars: usize) -> u64 {
+		envs(num_vars).iter().filter(|env| eval(e, env)).count() as u64
+	}
+
+	#[test]
+	fn sat_count_matches_truth_tables() {
+		let mut rng = SplitMix64::new(11);
+		for _ in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 7);
+			let e = rand_expr(&mut rng, nv, 5);
+			let f = from(&e, &rand_ord(&mut rng, nv));
+			let n = count(&e, nv);
+			assert_eq!(f.sat_count(nv), BigUint::from(n));
+			assert_eq!(f.not().sat_count(nv), BigUint::from((1 << nv) - n));
+			// vars past the order double the count each
+			assert_eq!(f.sat_count(nv + 70), BigUint::from(n).shl(70));
+		}
+	}
+
+	#[test]
+	fn sat_count_past_64_vars() {
+		// x == y on 64 bit words: one y per x
+		let (comp, _, good) = gen::comparator(64);
+		let mut m = Manager::new(&good);
+		let f = m.from_expr(&comp);
+		assert_eq!(m.sat_count(f, 128), BigUint::pow2(64));
+		let nf = m.not(f);
+		assert_eq!(m.sat_count(nf, 128), BigUint::pow2(128).sub(&BigUint::pow2(64)));
+		assert_eq!(m.sat_count(nf, 128).to_string(), "340282366920938463444927863358058659840");
+
+		// parity of 80 vars is true on half of them
+		let mut m = Manager::new(&(0..80).collect::<Vec<usize>>());
+		let mut parity = m.constant(false);
+		let mut all = m.constant(true);
+		let mut any = m.constant(false);
+		for x in 0..80 {
+			let v = m.var(x);
+			parity = m.xor(parity, v);
+			all = m.and(all, v);
+			any = m.or(any, v);
+		}
+		assert_eq!(m.sat_count(parity, 80), BigUint::pow2(79));
+		assert_eq!(m.sat_count(all, 80), BigUint::one());
+		assert_eq!(m.sat_count(any, 80), BigUint::pow2(80).sub(&BigUint::one()));
+		assert_eq!(m.sat_count(all, 100), BigUint::pow2(20));
+	}
+
+	// env agrees with every literal of the cube
+	fn in_cube(cube: &PartialEnv, env: &Env) -> bool {
+		cube.iter().all(|(&x, &b)| env[x] == b)
+	}
+
+	#[test]
+	fn any_sat_and_cubes_imply_the_function() {
+		let mut rng = SplitMix64::new(12);
+		for _ in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let e = rand_expr(&mut rng, nv, 5);
+			let f = from(&e, &rand_ord(&mut rng, nv));
+			let n = count(&e, nv);
+			let envs = envs(nv);
+			match f.any_sat() {
+				Some(cube) => assert!(envs.iter().filter(|env| in_cube(&cube, env)).all(|env| eval(&e, env))),
+				None => assert_eq!(n, 0),
+			}
+			assert_eq!(f.any_sat().is_some(), n > 0);
+			// disjoint cubes covering exactly the satisfying assignments
+			let cubes: Vec<PartialEnv> = f.sat_cubes().collect();
+			for env in &envs {
+				let hits = cubes.iter().filter(|cube| in_cube(cube, env)).count();
+				assert_eq!(hits, eval(&e, env) as usize);
+			}
+			let covered: u64 = cubes.iter().map(|cube| 1 << (nv - cube.len())).sum();
+			assert_eq!(covered, n);
+		}
+	}
+
+	#[test]
+	fn minterms_are_the_satisfying_assignments() {
+		let mut rng = SplitMix64::new(13);
+		for _ in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let e = rand_expr(&mut rng, nv, 5);
+			let f = from(&e, &rand_ord(&mut rng, nv));
+			let mut minterms: Vec<Env> = f.sat_minterms().collect();
+			assert!(minterms.iter().all(|env| eval(&e, env)));
+			assert_eq!(minterms.len() as u64, count(&e, nv));
+			minterms.sort();
+			minterms.dedup();
+			assert_eq!(minterms.len() as u64, count(&e, nv));
+		}
+	}
+
+	#[test]
+	fn samples_satisfy_and_repeat_by_seed() {
+		let mut rng = SplitMix64::new(14);
+		for _ in 0..100 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let e = rand_expr(&mut rng, nv, 5);
+			let f = from(&e, &rand_ord(&mut rng, nv));
+			let sampler = f.sampler();
+			let draw = |seed| {
+				let mut r = SplitMix64::new(seed);
+				(0..20).map(|_| sampler.sample(&mut r)).collect::<Vec<_>>()
+			};
+			let draws = draw(5);
+			assert_eq!(draws, draw(5));
+			if count(&e, nv) == 0 {
+				assert!(draws.iter().all(|d| d.is_none()));
+			} else {
+				assert!(draws.iter().all(|d| d.as_ref().is_some_and(|env| eval(&e, env))));
+			}
+		}
+	}
+
+	#[test]
+	fn samples_are_roughly_uniform() {
+		// x0 | (x1 & x2) over 4 vars, var 3 a don't care, 10 solutions
+		let e = bin(Var(0), Or, bin(Var(1), And, Var(2)));
+		let f = from(&e, &[2, 0, 3, 1]);
+		let sols: Vec<Env> = envs(4).into_iter().filter(|env| eval(&e, env)).collect();
+		assert_eq!(sols.len(), 10);
+		let mut rng = SplitMix64::new(15);
+		let draws = 4000;
+		let mut hits = vec![0usize; sols.len()];
+		for _ in 0..draws {
+			let env = f.sample(&mut rng).unwrap();
+			hits[sols.iter().position(|s| *s == env).unwrap()] += 1;
+		}
+		// each within a few standard deviations of its share
+		let exp = (draws / sols.len()) as f64;
+		for h in hits {
+			assert!((h as f64 - exp).abs() < 5.0 * exp.sqrt() + 3.0, "{} hits, expected {}", h, exp);
+		}
+	}
+
+	// uniform in [0, 1)
+	fn rand_f64(rng: &mut SplitMix64) -> f64 {
+		(rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	// product of the literal weights of env
+	fn env_weight<S: Semiring>(env: &Env, weights: &[(S, S)]) -> S {
+		env.iter().zip(weights).fold(S::one(), |acc, (&b, (w_x, w_nx))| acc.mul(if b { w_x } else { w_nx }))
+	}
+
+	#[test]
+	fn weighted_counts_match_brute_force() {
+		let mut rng = SplitMix64::new(16);
+		for _ in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let e = rand_expr(&mut rng, nv, 5);
+			let f = from(&e, &rand_ord(&mut rng, nv));
+			let sols: Vec<Env> = envs(nv).into_iter().filter(|env| eval(&e, env)).collect();
+
+			let p: Vec<f64> = (0..nv).map(|_| rand_f64(&mut rng)).collect();
+			let probs: Vec<(f64, f64)> = p.iter().map(|&p_x| (p_x, 1.0 - p_x)).collect();
+			let expected: f64 = sols.iter().map(|env| env_weight(env, &probs)).sum();
+			assert!((f.probability(&p) - expected).abs() < 1e-9);
+
+			let weights: Vec<(BigUint, BigUint)> = (0..nv)
+				.map(|_| (BigUint::from(rng.next_u64() % 1000), BigUint::from(rng.next_u64() % 1000)))
+				.collect();
+			let expected = sols.iter().fold(BigUint::zero(), |acc, env| acc.add(&env_weight(env, &weights)));
+			assert_eq!(f.weighted_count(&weights), expected);
+
+			// unit weights count the solutions
+			let ones = vec![(BigUint::one(), BigUint::one()); nv];
+			assert_eq!(f.weighted_count(&ones), f.sat_count(nv));
+		}
+	}
+
+	#[test]
+	fn min_cost_sat_matches_brute_force() {
+		let mut rng = SplitMix64::new(17);
+		for _ in 0..200 {
+			let nv = 1 + rand_below(&mut rng, 6);
+			let e = rand_expr(&mut rng, nv, 5);
+			let f = from(&e, &rand_ord(&mut rng, nv));
+			// whole costs, some negative, so sums are exact
+			let costs: Vec<(f64, f64)> = (0..nv)
+				.map(|_| (rand_below(&mut rng, 15) as f64 - 5.0, rand_below(&mut rng, 15) as f64 - 5.0))
+				.collect();
+			let cost = |env: &Env| -> f64 {
+				env.iter().zip(&costs).map(|(&b, &(c_x, c_nx))| if b { c_x } else { c_nx }).sum()
+			};
+			let best = envs(nv).iter().filter(|env| eval(&e, env)).map(cost).reduce(f64::min);
+			match f.min_cost_sat(&costs) {
+				Some((env, c)) => {
+					assert!(eval(&e, &env));
+					assert_eq!(cost(&env), c);
+					assert_eq!(Some(c), best);
+				},
+				None => assert_eq!(best, None),
+			}
+		}
+	}
+
+	#[test]
+	#[should_panic]
+	fn sat_count_over_fewer_vars_panics() {
+		from(&Var(2), &[2, 0, 1]).sat_count(2);
+	}
+}
@@ -0,0 +1,96 @@
+// Fixtures shared by the unit tests
+use crate::expr::*;
+use crate::manager::Manager;
+use crate::neg_arc_bdd::FunctionNode;
+use crate::rng::{Rng, SplitMix64};
+use Expr::*;
+use BOp::*;
+
+// vars of a 3 bit comparator, which exprs are over
+pub const NUM_VARS: usize = 6;
+
+// functions sharing sub-graphs, over the vars of a 3 bit comparator
+pub fn exprs() -> Vec<Expr> {
+	let (comp, _, _) = gen::comparator(3);
+	vec![
+		comp.clone(),
+		bin(comp, And, Var(4)),
+		bin(Var(0), Or, bin(Var(3), And, not(Var(5)))),
+		bin(bin(Var(1), And, Var(2)), Or, bin(not(Var(1)), And, Var(4))),
+	]
+}
+
+// order of the comparator with all x bits above all y bits
+pub fn bad_order() -> Vec<usize> {
+	gen::comparator(3).1
+}
+
+// every assignment to num_vars vars
+pub fn envs(num_vars: usize) -> Vec<Env> {
+	(0..1usize << num_vars)
+		.map(|i| (0..num_vars).map(|x| (i >> x) & 1 == 1).collect())
+		.collect()
+}
+
+// each expression built in m and protected
+pub fn build(m: &mut Manager, es: &[Expr]) -> Vec<FunctionNode> {
+	es.iter()
+		.map(|e| {
+			let f = m.from_expr(e);
+			m.protect(f);
+			f
+		})
+		.collect()
+}
+
+// a manager in var_ord holding just the protected expressions
+pub fn fresh(es: &[Expr], var_ord: &[usize]) -> (Manager, Vec<FunctionNode>) {
+	let mut m = Manager::new(var_ord);
+	let fs = build(&mut m, es);
+	m.gc();
+	(m, fs)
+}
+
+// every function still evaluates as its expression, and the table holds
+// exactly the nodes a fresh build in the current order does
+pub fn check(m: &Manager, es: &[Expr], fs: &[FunctionNode]) {
+	for env in envs(m.num_vars()) {
+		for (e, &f) in es.iter().zip(fs) {
+			assert_eq!(m.eval(f, &env), eval(e, &env));
+		}
+	}
+	let (fresh, _) = fresh(es, m.var_ord());
+	assert_eq!(m.live_node_count(), fresh.live_node_count());
+}
+
+// uniform in [0, n)
+pub fn rand_below(rng: &mut SplitMix64, n: usize) -> usize {
+	(rng.next_u64() % n as u64) as usize
+}
+
+// random expression over num_vars vars, at most depth deep
+pub fn rand_expr(rng: &mut SplitMix64, num_vars: usize, depth: usize) -> Expr {
+	if depth == 0 || rand_below(rng, 5) == 0 {
+		if rand_below(rng, 8) == 0 { Lit(rng.next_bool()) } else { Var(rand_below(rng, num_vars)) }
+	} else {
+		match rand_below(rng, 3) {
+			0 => not(rand_expr(rng, num_vars, depth - 1)),
+			1 => bin(rand_expr(rng, num_vars, depth - 1), And, rand_expr(rng, num_vars, depth - 1)),
+			_ => bin(rand_expr(rng, num_vars, depth - 1), Or, rand_expr(rng, num_vars, depth - 1)),
+		}
+	}
+}
+
+// random order of num_vars vars
+pub fn rand_ord(rng: &mut SplitMix64, num_vars: usize) -> Vec<usize> {
+	let mut ord: Vec<usize> = (0..num_vars).collect();
+	for i in (1..num_vars).rev() {
+		ord.swap(i, rand_below(rng, i + 1));
+	}
+	ord
+}
+
+// random subset of num_vars vars
+pub fn rand_vars(rng: &mut SplitMix64, num_vars: usize) -> Vec<usize> {
+	(0..num_vars).filter(|_| rng.next_bool()).collect()
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use crate::neg_arc_bdd::*;
+
+// arcs of a node, its label is implied by the subtable holding it
+type Arcs = (NodeIdx, NodeIdx, bool);
+
+// Unique table split into one subtable per variable.
+// Reordering only touches the subtables of the two vars being swapped, and
+// the nodes labeled with a var can be listed without walking the graph.
+#[derive(Clone, Debug)]
+pub struct UniqueTable {
+	subtables: Vec<HashMap<Arcs, NodeIdx>>,
+	len: usize,
+}
+
+impl UniqueTable {
+
+	pub fn new(num_vars: usize) -> UniqueTable {
+		UniqueTable {
+			subtables: vec![HashMap::new(); num_vars],
+			len: 0,
+		}
+	}
+
+	pub fn get(&self, node: &InternalNode) -> Option<NodeIdx> {
+		self.subtables[node.label].get(&arcs(node)).cloned()
+	}
+
+	pub fn insert(&mut self, node: &InternalNode, n: NodeIdx) {
+		if self.subtables[node.label].insert(arcs(node), n).is_none() {
+			self.len += 1;
+		}
+	}
+
+	pub fn remove(&mut self, node: &InternalNode) -> Option<NodeIdx> {
+		let n = self.subtables[node.label].remove(&arcs(node));
+		if n.is_some() {
+			self.len -= 1;
+		}
+		n
+	}
+
+	// nodes in every subtable
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	// nodes labeled x
+	pub fn var_len(&self, x: usize) -> usize {
+		self.subtables[x].len()
+	}
+
+	pub fn var_nodes(&self, x: usize) -> Vec<NodeIdx> {
+		self.subtables[x].values().cloned().collect()
+	}
+
+	// Keep only the nodes f accepts. Subtables left mostly empty give their
+	// memory back, they grow again on insert.
+	pub fn retain<F>(&mut self, mut f: F) where F: FnMut(NodeIdx) -> bool {
+		self.len = 0;
+		for subtable in self.subtables.iter_mut() {
+			subtable.retain(|_, n| f(*n));
+			if subtable.capacity() > 4 * subtable.len() {
+				subtable.shrink_to_fit();
+			}
+			self.len += subtable.len();
+		}
+	}
+}
+
+fn arcs(node: &InternalNode) -> Arcs {
+	(node.t_arc, node.e_arc, node.e_complement)
+}